@@ -1,15 +1,797 @@
 #[macro_use]
 extern crate serde;
 use candid::{Decode, Encode};
-use ic_cdk::api::time;
+use ic_cdk::api::{caller, is_controller, time};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{BoundedStorable, Cell, DefaultMemoryImpl, StableBTreeMap, Storable};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::{borrow::Cow, cell::RefCell};
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 type IdCell = Cell<u64, Memory>;
 
+const NANOS_PER_HOUR: u64 = 3_600 * 1_000_000_000;
+const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+
+// Bounds keeping free-text fields of 512-byte records within their storage size
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_NOTES_LENGTH: usize = 128;
+const MAX_SHIPMENT_PRODUCTS: usize = 20;
+
+// Physically possible measurement ranges; acceptance criteria narrow these further
+const PH_RANGE: (f64, f64) = (0.0, 14.0);
+const TEMPERATURE_RANGE: (f64, f64) = (-50.0, 100.0);
+
+// Longest withdrawal period accepted for a veterinary treatment (ten years)
+const MAX_WITHDRAWAL_DAYS: u32 = 3650;
+
+// Diseases that trigger a plant lockdown when suspected
+const NOTIFIABLE_DISEASES: [&str; 5] = ["FMD", "BSE", "ASF", "CSF", "anthrax"];
+
+// Upper bound keeping a consignment within its storage size
+const MAX_CONSIGNMENT_ANIMALS: usize = 800;
+
+// Bounds keeping price grids and settlements within their storage size
+const MAX_GRID_ADJUSTMENTS: usize = 50;
+const MAX_SETTLEMENT_DEDUCTIONS: usize = 10;
+
+// Settlement statuses and the statuses each may move to
+const SETTLEMENT_TRANSITIONS: [(SettlementStatus, &[SettlementStatus]); 4] = [
+    (
+        SettlementStatus::Pending,
+        &[SettlementStatus::Approved, SettlementStatus::Disputed],
+    ),
+    (SettlementStatus::Disputed, &[SettlementStatus::Pending]),
+    (
+        SettlementStatus::Approved,
+        &[SettlementStatus::Paid, SettlementStatus::Disputed],
+    ),
+    (SettlementStatus::Paid, &[]),
+];
+
+// Country prefixes accepted on UK/EU visual ear tags
+const VISUAL_TAG_COUNTRIES: [&str; 30] = [
+    "UK", "GB", "XI", "IE", "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "ES", "FI", "FR", "GR",
+    "HR", "HU", "IT", "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+// Bounds keeping inspection templates, answers and verdicts within their storage size
+const MAX_TEMPLATE_QUESTIONS: usize = 30;
+const MAX_QUESTION_CODE_LENGTH: usize = 32;
+const MAX_QUESTION_TEXT_LENGTH: usize = 200;
+const MAX_PHOTO_REFERENCE_LENGTH: usize = 200;
+
+// Bound on species aliases and allowed product types, keeping entries within their storage size
+const MAX_SPECIES_LIST_ENTRIES: usize = 20;
+
+// Bound keeping a non-conformance report within its storage size
+const MAX_NCR_TEXT_LENGTH: usize = 1000;
+
+// Longest monitoring interval accepted for a critical control point (one week)
+const MAX_MONITORING_INTERVAL_MINUTES: u64 = 7 * 24 * 60;
+
+// Control chart constants per subgroup size n = 2..=10: (d2, D3, D4)
+const CONTROL_CHART_CONSTANTS: [(f64, f64, f64); 9] = [
+    (1.128, 0.0, 3.267),
+    (1.693, 0.0, 2.574),
+    (2.059, 0.0, 2.282),
+    (2.326, 0.0, 2.114),
+    (2.534, 0.0, 2.004),
+    (2.704, 0.076, 1.924),
+    (2.847, 0.136, 1.864),
+    (2.970, 0.184, 1.816),
+    (3.078, 0.223, 1.777),
+];
+
+// Certification an employee needs to perform quality inspections
+const INSPECTOR_CERTIFICATION: &str = "meat_inspector";
+
+// Attachment storage limits; chunks stay well below the ingress message limit
+const MAX_BLOB_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_CHUNK_SIZE: usize = 256 * 1024;
+const MAX_FILE_NAME_LENGTH: usize = 255;
+const ALLOWED_MIME_TYPES: [&str; 5] = [
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/heic",
+    "application/pdf",
+];
+
+// Records files can be attached to
+const ATTACHMENT_RECORD_TYPES: [&str; 5] = [
+    "quality_inspection",
+    "maintenance",
+    "shipment",
+    "waste",
+    "waste_manifest",
+];
+
+// Instrument type that takes each kind of reading
+const READING_INSTRUMENT_TYPES: [(&str, InstrumentType); 3] = [
+    ("temperature", InstrumentType::Thermometer),
+    ("ph", InstrumentType::PhMeter),
+    ("weight", InstrumentType::Scale),
+];
+
+// Readings rejected outright when the instrument is out of calibration; others are flagged
+const REJECT_UNCALIBRATED_READINGS: [&str; 2] = ["temperature", "ph"];
+
+// Longest calibration interval accepted for an instrument (ten years)
+const MAX_CALIBRATION_INTERVAL_DAYS: u64 = 3650;
+
+// Waste records carried under one transfer manifest
+const MAX_MANIFEST_RECORDS: usize = 50;
+
+// Expense category tree depth, counting top-level categories as depth 0
+const MAX_EXPENSE_CATEGORY_DEPTH: u32 = 3;
+
+// Expense category receiving legacy expenses whose category is not in the tree
+const UNCATEGORISED_EXPENSES: &str = "uncategorised";
+
+// Expense categories that maintenance and waste costs are reported under
+const MAINTENANCE_EXPENSE_CATEGORIES: [(MaintenanceType, &str); 4] = [
+    (MaintenanceType::Preventive, "maintenance_planned"),
+    (MaintenanceType::Inspection, "maintenance_planned"),
+    (MaintenanceType::Corrective, "maintenance_repairs"),
+    (MaintenanceType::Emergency, "maintenance_repairs"),
+];
+const LABOR_EXPENSE_CATEGORY: &str = "labor";
+const WASTE_EXPENSE_CATEGORIES: [(AbpCategory, &str); 3] = [
+    (AbpCategory::Category1, "waste_category_1"),
+    (AbpCategory::Category2, "waste_category_2"),
+    (AbpCategory::Category3, "waste_category_3"),
+];
+
+// Utility meter types with their unit and an indicative emission factor in kg CO2e per unit
+const METER_TYPES: [(MeterType, &str, f64); 3] = [
+    (MeterType::Water, "litres", 0.000_149),
+    (MeterType::Electricity, "kWh", 0.207),
+    (MeterType::Gas, "kWh", 0.183),
+];
+
+// Disposal routes permitted for each animal by-product category (Regulation (EC) No 1069/2009)
+const ABP_DISPOSAL_ROUTES: [(AbpCategory, &[&str]); 3] = [
+    (
+        AbpCategory::Category1,
+        &["incineration", "co_incineration", "rendering_to_landfill"],
+    ),
+    (
+        AbpCategory::Category2,
+        &[
+            "incineration",
+            "co_incineration",
+            "rendering_to_landfill",
+            "rendering",
+            "composting",
+            "biogas",
+        ],
+    ),
+    (
+        AbpCategory::Category3,
+        &[
+            "incineration",
+            "co_incineration",
+            "rendering_to_landfill",
+            "rendering",
+            "composting",
+            "biogas",
+            "pet_food",
+            "animal_feed",
+        ],
+    ),
+];
+
+// Status and type enums
+
+// Text labels of an enum, as stored before the enums existed and shown in analytics
+trait StatusLabel: Copy + 'static {
+    const ALL: &'static [Self];
+
+    fn as_str(self) -> &'static str;
+
+    // Accepts legacy spellings such as "In Progress" or "in_stock"
+    fn from_label(label: &str) -> Option<Self> {
+        let normalized = label.trim().to_lowercase().replace(['_', ' '], "-");
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|value| value.as_str() == normalized)
+    }
+}
+
+// Processing status of an animal
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum AnimalStatus {
+    #[default]
+    Received,
+    Processed,
+    Disposed,
+}
+
+impl StatusLabel for AnimalStatus {
+    const ALL: &'static [Self] = &[
+        AnimalStatus::Received,
+        AnimalStatus::Processed,
+        AnimalStatus::Disposed,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            AnimalStatus::Received => "received",
+            AnimalStatus::Processed => "processed",
+            AnimalStatus::Disposed => "disposed",
+        }
+    }
+}
+
+// Stock status of a meat product
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum ProductStatus {
+    #[default]
+    InStock,
+    Sold,
+    Disposed,
+}
+
+impl StatusLabel for ProductStatus {
+    const ALL: &'static [Self] = &[
+        ProductStatus::InStock,
+        ProductStatus::Sold,
+        ProductStatus::Disposed,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ProductStatus::InStock => "in-stock",
+            ProductStatus::Sold => "sold",
+            ProductStatus::Disposed => "disposed",
+        }
+    }
+}
+
+// Employment status of an employee
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum EmployeeStatus {
+    #[default]
+    Active,
+    Inactive,
+    Suspended,
+}
+
+impl StatusLabel for EmployeeStatus {
+    const ALL: &'static [Self] = &[
+        EmployeeStatus::Active,
+        EmployeeStatus::Inactive,
+        EmployeeStatus::Suspended,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            EmployeeStatus::Active => "active",
+            EmployeeStatus::Inactive => "inactive",
+            EmployeeStatus::Suspended => "suspended",
+        }
+    }
+}
+
+// Progress of a maintenance record
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum MaintenanceStatus {
+    #[default]
+    Scheduled,
+    InProgress,
+    Completed,
+}
+
+impl StatusLabel for MaintenanceStatus {
+    const ALL: &'static [Self] = &[
+        MaintenanceStatus::Scheduled,
+        MaintenanceStatus::InProgress,
+        MaintenanceStatus::Completed,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            MaintenanceStatus::Scheduled => "scheduled",
+            MaintenanceStatus::InProgress => "in-progress",
+            MaintenanceStatus::Completed => "completed",
+        }
+    }
+}
+
+// Kind of maintenance work
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum MaintenanceType {
+    #[default]
+    Preventive,
+    Corrective,
+    Emergency,
+    Inspection,
+}
+
+impl StatusLabel for MaintenanceType {
+    const ALL: &'static [Self] = &[
+        MaintenanceType::Preventive,
+        MaintenanceType::Corrective,
+        MaintenanceType::Emergency,
+        MaintenanceType::Inspection,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            MaintenanceType::Preventive => "preventive",
+            MaintenanceType::Corrective => "corrective",
+            MaintenanceType::Emergency => "emergency",
+            MaintenanceType::Inspection => "inspection",
+        }
+    }
+}
+
+// Delivery status of a shipment
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum ShipmentStatus {
+    #[default]
+    Preparing,
+    InTransit,
+    Delivered,
+    Cancelled,
+}
+
+impl StatusLabel for ShipmentStatus {
+    const ALL: &'static [Self] = &[
+        ShipmentStatus::Preparing,
+        ShipmentStatus::InTransit,
+        ShipmentStatus::Delivered,
+        ShipmentStatus::Cancelled,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ShipmentStatus::Preparing => "preparing",
+            ShipmentStatus::InTransit => "in-transit",
+            ShipmentStatus::Delivered => "delivered",
+            ShipmentStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+// Kind of welfare event recorded between arrival and stunning
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum WelfareEventType {
+    #[default]
+    Transport,
+    WaterAccess,
+    FeedAccess,
+    Stun,
+    ReStun,
+    DeadOnArrival,
+}
+
+impl StatusLabel for WelfareEventType {
+    const ALL: &'static [Self] = &[
+        WelfareEventType::Transport,
+        WelfareEventType::WaterAccess,
+        WelfareEventType::FeedAccess,
+        WelfareEventType::Stun,
+        WelfareEventType::ReStun,
+        WelfareEventType::DeadOnArrival,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            WelfareEventType::Transport => "transport",
+            WelfareEventType::WaterAccess => "water-access",
+            WelfareEventType::FeedAccess => "feed-access",
+            WelfareEventType::Stun => "stun",
+            WelfareEventType::ReStun => "re-stun",
+            WelfareEventType::DeadOnArrival => "dead-on-arrival",
+        }
+    }
+}
+
+// Approval status of a supplier settlement
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum SettlementStatus {
+    #[default]
+    Pending,
+    Approved,
+    Paid,
+    Disputed,
+}
+
+impl StatusLabel for SettlementStatus {
+    const ALL: &'static [Self] = &[
+        SettlementStatus::Pending,
+        SettlementStatus::Approved,
+        SettlementStatus::Paid,
+        SettlementStatus::Disputed,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SettlementStatus::Pending => "pending",
+            SettlementStatus::Approved => "approved",
+            SettlementStatus::Paid => "paid",
+            SettlementStatus::Disputed => "disputed",
+        }
+    }
+}
+
+// Record a laboratory sample is taken from
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum LabSubjectType {
+    #[default]
+    Animal,
+    Product,
+}
+
+impl StatusLabel for LabSubjectType {
+    const ALL: &'static [Self] = &[LabSubjectType::Animal, LabSubjectType::Product];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LabSubjectType::Animal => "animal",
+            LabSubjectType::Product => "product",
+        }
+    }
+}
+
+// Regulatory laboratory test
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum LabTestType {
+    #[default]
+    Residue,
+    EColi,
+    Salmonella,
+    Listeria,
+}
+
+impl StatusLabel for LabTestType {
+    const ALL: &'static [Self] = &[
+        LabTestType::Residue,
+        LabTestType::EColi,
+        LabTestType::Salmonella,
+        LabTestType::Listeria,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LabTestType::Residue => "residue",
+            LabTestType::EColi => "e-coli",
+            LabTestType::Salmonella => "salmonella",
+            LabTestType::Listeria => "listeria",
+        }
+    }
+}
+
+// Chain-of-custody status of a laboratory sample
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum LabSampleStatus {
+    #[default]
+    Taken,
+    Sent,
+    Received,
+    Resulted,
+}
+
+impl StatusLabel for LabSampleStatus {
+    const ALL: &'static [Self] = &[
+        LabSampleStatus::Taken,
+        LabSampleStatus::Sent,
+        LabSampleStatus::Received,
+        LabSampleStatus::Resulted,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LabSampleStatus::Taken => "taken",
+            LabSampleStatus::Sent => "sent",
+            LabSampleStatus::Received => "received",
+            LabSampleStatus::Resulted => "resulted",
+        }
+    }
+}
+
+// Outcome of a laboratory test
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum LabResult {
+    #[default]
+    Pending,
+    Negative,
+    Positive,
+}
+
+impl StatusLabel for LabResult {
+    const ALL: &'static [Self] = &[LabResult::Pending, LabResult::Negative, LabResult::Positive];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LabResult::Pending => "pending",
+            LabResult::Negative => "negative",
+            LabResult::Positive => "positive",
+        }
+    }
+}
+
+// Answer expected by an inspection template question
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum AnswerType {
+    #[default]
+    YesNo,
+    Score,
+    Numeric,
+    Photo,
+}
+
+impl StatusLabel for AnswerType {
+    const ALL: &'static [Self] = &[
+        AnswerType::YesNo,
+        AnswerType::Score,
+        AnswerType::Numeric,
+        AnswerType::Photo,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            AnswerType::YesNo => "yes-no",
+            AnswerType::Score => "score",
+            AnswerType::Numeric => "numeric",
+            AnswerType::Photo => "photo",
+        }
+    }
+}
+
+// Status of a critical limit deviation
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum DeviationStatus {
+    #[default]
+    Open,
+    Closed,
+}
+
+impl StatusLabel for DeviationStatus {
+    const ALL: &'static [Self] = &[DeviationStatus::Open, DeviationStatus::Closed];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            DeviationStatus::Open => "open",
+            DeviationStatus::Closed => "closed",
+        }
+    }
+}
+
+// Record a non-conformance report can be raised against
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum NcrLinkType {
+    #[default]
+    QualityInspection,
+    Shipment,
+    Maintenance,
+    Supplier,
+}
+
+impl StatusLabel for NcrLinkType {
+    const ALL: &'static [Self] = &[
+        NcrLinkType::QualityInspection,
+        NcrLinkType::Shipment,
+        NcrLinkType::Maintenance,
+        NcrLinkType::Supplier,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            NcrLinkType::QualityInspection => "quality-inspection",
+            NcrLinkType::Shipment => "shipment",
+            NcrLinkType::Maintenance => "maintenance",
+            NcrLinkType::Supplier => "supplier",
+        }
+    }
+}
+
+// Severity of a non-conformance
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum NcrSeverity {
+    #[default]
+    Minor,
+    Major,
+    Critical,
+}
+
+impl StatusLabel for NcrSeverity {
+    const ALL: &'static [Self] = &[
+        NcrSeverity::Minor,
+        NcrSeverity::Major,
+        NcrSeverity::Critical,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            NcrSeverity::Minor => "minor",
+            NcrSeverity::Major => "major",
+            NcrSeverity::Critical => "critical",
+        }
+    }
+}
+
+// Progress of a non-conformance report
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum NcrStatus {
+    #[default]
+    Open,
+    Investigated,
+    Closed,
+}
+
+impl StatusLabel for NcrStatus {
+    const ALL: &'static [Self] = &[NcrStatus::Open, NcrStatus::Investigated, NcrStatus::Closed];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            NcrStatus::Open => "open",
+            NcrStatus::Investigated => "investigated",
+            NcrStatus::Closed => "closed",
+        }
+    }
+}
+
+// Kind of CAPA action
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum CapaActionType {
+    #[default]
+    Corrective,
+    Preventive,
+}
+
+impl StatusLabel for CapaActionType {
+    const ALL: &'static [Self] = &[CapaActionType::Corrective, CapaActionType::Preventive];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CapaActionType::Corrective => "corrective",
+            CapaActionType::Preventive => "preventive",
+        }
+    }
+}
+
+// Progress of a CAPA action
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum CapaStatus {
+    #[default]
+    Open,
+    Completed,
+}
+
+impl StatusLabel for CapaStatus {
+    const ALL: &'static [Self] = &[CapaStatus::Open, CapaStatus::Completed];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CapaStatus::Open => "open",
+            CapaStatus::Completed => "completed",
+        }
+    }
+}
+
+// Upload status of an attachment blob
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum BlobStatus {
+    #[default]
+    Uploading,
+    Complete,
+    Rejected,
+}
+
+impl StatusLabel for BlobStatus {
+    const ALL: &'static [Self] = &[
+        BlobStatus::Uploading,
+        BlobStatus::Complete,
+        BlobStatus::Rejected,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BlobStatus::Uploading => "uploading",
+            BlobStatus::Complete => "complete",
+            BlobStatus::Rejected => "rejected",
+        }
+    }
+}
+
+// Kind of measuring instrument
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum InstrumentType {
+    #[default]
+    Thermometer,
+    PhMeter,
+    Scale,
+}
+
+impl StatusLabel for InstrumentType {
+    const ALL: &'static [Self] = &[
+        InstrumentType::Thermometer,
+        InstrumentType::PhMeter,
+        InstrumentType::Scale,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            InstrumentType::Thermometer => "thermometer",
+            InstrumentType::PhMeter => "ph-meter",
+            InstrumentType::Scale => "scale",
+        }
+    }
+}
+
+// Service status of an instrument
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum InstrumentStatus {
+    #[default]
+    Active,
+    Retired,
+}
+
+impl StatusLabel for InstrumentStatus {
+    const ALL: &'static [Self] = &[InstrumentStatus::Active, InstrumentStatus::Retired];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            InstrumentStatus::Active => "active",
+            InstrumentStatus::Retired => "retired",
+        }
+    }
+}
+
+// Utility metered on site
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum MeterType {
+    #[default]
+    Water,
+    Electricity,
+    Gas,
+}
+
+impl StatusLabel for MeterType {
+    const ALL: &'static [Self] = &[MeterType::Water, MeterType::Electricity, MeterType::Gas];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            MeterType::Water => "water",
+            MeterType::Electricity => "electricity",
+            MeterType::Gas => "gas",
+        }
+    }
+}
+
+// Animal by-product category, from highest to lowest risk
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum AbpCategory {
+    #[default]
+    Category1, // e.g., specified risk material, TSE suspects
+    Category2, // e.g., condemned carcasses, animals dead other than by slaughter
+    Category3, // fit for human consumption at slaughter but not intended for it
+}
+
+impl AbpCategory {
+    fn label(self) -> &'static str {
+        match self {
+            AbpCategory::Category1 => "Category 1",
+            AbpCategory::Category2 => "Category 2",
+            AbpCategory::Category3 => "Category 3",
+        }
+    }
+}
+
+// Basis on which an employee is paid
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum PayType {
+    #[default]
+    Hourly, // rate per hour worked
+    Salaried,  // annual salary, prorated over the payroll period
+    PieceRate, // rate per animal processed
+}
+
 // Slaughterhouse struct
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
 struct Slaughterhouse {
@@ -28,10 +810,10 @@ struct Animal {
     id: u64,
     slaughterhouse_id: u64,
     tag_number: String,
-    species: String, // e.g., cow, sheep, goat, pig
+    species: String, // canonical species code from the catalog, e.g., cattle, sheep, goat, pig
     weight: f64,     // in kilograms
     arrival_time: u64,
-    status: String, // "received", "processed", "disposed"
+    status: AnimalStatus,
 }
 
 // MeatProduct struct
@@ -44,7 +826,7 @@ struct MeatProduct {
     weight: f64,          // in kilograms
     price_per_kg: f64,
     total_price: f64,
-    status: String, // "in-stock", "sold", "disposed"
+    status: ProductStatus,
     created_at: u64,
 }
 
@@ -64,6 +846,7 @@ struct QualityInspection {
     id: u64,
     animal_id: u64,
     inspector_name: String,
+    inspector_id: Option<u64>, // employee; absent on inspections predating inspector accounts
     inspection_date: u64,
     temperature: f64,
     ph_level: f64,
@@ -81,7 +864,8 @@ struct Employee {
     certification: String,
     hire_date: u64,
     contact: String,
-    status: String, // active, inactive, suspended
+    status: EmployeeStatus,
+    supervisor: bool, // may override inspection verdicts
 }
 
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
@@ -89,12 +873,12 @@ struct MaintenanceRecord {
     id: u64,
     slaughterhouse_id: u64,
     equipment_name: String,
-    maintenance_type: String,
+    maintenance_type: MaintenanceType,
     cost: f64,
     date: u64,
     next_maintenance_date: u64,
     performed_by: String,
-    status: String, // scheduled, in-progress, completed
+    status: MaintenanceStatus,
     notes: String,
 }
 
@@ -119,7 +903,7 @@ struct Shipment {
     shipping_date: u64,
     expected_delivery: u64,
     temperature_log: Vec<f64>,
-    status: String,
+    status: ShipmentStatus,
     tracking_number: String,
 }
 
@@ -128,998 +912,10537 @@ struct WasteRecord {
     id: u64,
     slaughterhouse_id: u64,
     waste_type: String,
-    quantity: f64,
+    quantity: f64, // in kilograms
     disposal_method: String,
     disposal_date: u64,
     handled_by: String,
     cost: f64,
+    category: Option<AbpCategory>, // unset on records made before categories were tracked
+    source_animal_id: Option<u64>,
+    source_product_id: Option<u64>, // condemned product
 }
 
+// Veterinary treatment declared by the supplier for an animal
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct FinancialMetrics {
-    total_revenue: f64,
-    total_expenses: f64,
-    profit_margin: f64,
-    operating_costs: f64,
-    maintenance_costs: f64,
-    labor_costs: f64,
-    waste_management_costs: f64,
-    revenue_by_product: HashMap<String, f64>,
-    expenses_by_category: HashMap<String, f64>,
+struct VeterinaryTreatment {
+    id: u64,
+    animal_id: u64,
+    drug_name: String,
+    dose: f64,
+    dose_unit: String, // e.g., ml, mg/kg
+    administration_date: u64,
+    withdrawal_days: u32,
+    withdrawal_end_date: u64, // earliest time the animal may be slaughtered
+    declared_by: String,
+    declared_at: u64,
 }
 
+// Welfare event recorded for an animal between arrival and stunning
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct QualityMetrics {
-    total_inspections: u32,
-    passed_inspections: u32,
-    failure_rate: f64,
-    average_temperature: f64,
-    average_ph_level: f64,
-    inspections: Vec<QualityInspection>,
+struct WelfareEvent {
+    id: u64,
+    animal_id: u64,
+    slaughterhouse_id: u64,
+    event_type: WelfareEventType,
+    event_time: u64,
+    duration_hours: f64, // transport duration, or lairage duration for stun events
+    effective: bool,     // stun effectiveness
+    recorded_by: String,
+    notes: String,
+    flagged: bool,
+    flag_reason: String,
 }
 
+// Per-slaughterhouse welfare limits used to flag events
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct MaintenanceAnalytics {
-    total_maintenance_cost: f64,
-    maintenance_by_type: HashMap<String, u32>,
-    equipment_reliability: HashMap<String, f64>,
-    pending_maintenance: Vec<MaintenanceRecord>,
-    equipment_history: HashMap<String, Vec<MaintenanceRecord>>,
+struct WelfareLimits {
+    slaughterhouse_id: u64,
+    max_transport_hours: f64,
+    max_lairage_hours: f64,
+    feed_required_after_hours: f64,
+    updated_at: u64,
 }
 
+// Notifiable disease lockdown of a slaughterhouse
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct InventoryAnalytics {
-    product_counts: HashMap<String, u32>,
-    total_inventory_value: f64,
-    products_by_status: HashMap<String, Vec<MeatProduct>>,
-    low_stock_items: Vec<MeatProduct>,
+struct Lockdown {
+    id: u64,
+    slaughterhouse_id: u64,
+    disease: String, // see NOTIFIABLE_DISEASES
+    suspected_animal_id: u64,
+    exposure_cutoff: u64, // products processed since this time are quarantined
+    reason: String,
+    declared_by: String, // principal of the declaring caller
+    declared_at: u64,
+    active: bool,
+    lifted_by: String,
+    lifted_at: u64,
+    lift_reason: String,
 }
 
-// Payload structs
+// Delivery of animals on one vehicle under one movement document
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct CreateSlaughterhousePayload {
-    name: String,
-    location: String,
-    contact: String,
-    email: String,
-    capacity: u64,
+struct Consignment {
+    id: u64,
+    slaughterhouse_id: u64,
+    supplier_id: u64,
+    haulier: String,
+    vehicle_plate: String,
+    movement_permit_number: String,
+    departure_time: u64,
+    arrival_time: u64,
+    headcount_declared: u32,
+    headcount_received: u32,
+    animal_ids: Vec<u64>,
+    created_at: u64,
 }
 
+// Carcass classification of a slaughtered animal
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct RegisterAnimalPayload {
+struct CarcassGrade {
+    id: u64,
+    animal_id: u64,
     slaughterhouse_id: u64,
-    tag_number: String,
+    scheme: String, // see GRADING_SCHEMES
+    carcass_weight: f64,
+    conformation: String,  // EU SEUROP conformation class
+    fat_class: String,     // EU SEUROP fat class
+    quality_grade: String, // USDA quality grade
+    yield_grade: String,   // USDA yield grade
+    lean_meat_percentage: f64,
+    grade: String, // combined label, e.g. R3, Choice YG2
+    graded_by: String,
+    graded_at: u64,
+}
+
+// Premium (positive) or penalty (negative) per kg for a carcass grade
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct GradeAdjustment {
+    grade: String,
+    adjustment_per_kg: f64,
+}
+
+// Carcass price grid for a species and grading scheme over a validity period
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PriceGrid {
+    id: u64,
     species: String,
-    weight: f64,
+    scheme: String,
+    valid_from: u64,
+    valid_to: u64,
+    base_price_per_kg: f64,
+    adjustments: Vec<GradeAdjustment>,
+    created_at: u64,
 }
 
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct CreateMeatProductPayload {
-    animal_id: u64,
-    slaughterhouse_id: u64,
-    product_type: String,
-    weight: f64,
-    price_per_kg: f64,
+struct SettlementDeduction {
+    description: String, // e.g., levy, haulage, tag replacement
+    amount: f64,
 }
 
+// Payment statement to a supplier for one consignment
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct RecordExpensePayload {
+struct SettlementStatement {
+    id: u64,
+    consignment_id: u64,
+    supplier_id: u64,
     slaughterhouse_id: u64,
-    category: String,
-    amount: f64,
-    description: String,
+    animals_paid: u32,
+    animals_condemned: u32,
+    total_carcass_weight: f64,
+    gross_amount: f64,
+    deductions: Vec<SettlementDeduction>,
+    total_deductions: f64,
+    net_amount: f64,
+    status: SettlementStatus,
+    created_at: u64,
+    status_updated_at: u64,
 }
 
+// Per-animal line of a settlement statement
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct QualityInspectionPayload {
+struct SettlementLine {
+    id: u64,
+    settlement_id: u64,
     animal_id: u64,
-    inspector_name: String,
-    temperature: f64,
-    ph_level: f64,
-    visual_inspection: String,
-    passed: bool,
-    notes: String,
+    tag_number: String,
+    grade: String,
+    carcass_weight: f64,
+    price_per_kg: f64,
+    amount: f64,
+    condemned: bool,
 }
 
+// Catalog entry with the rules applied to animals and products of a species
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct EmployeePayload {
-    slaughterhouse_id: u64,
+struct Species {
+    id: u64,
+    code: String, // canonical, lowercase
     name: String,
-    role: String,
-    certification: String,
-    contact: String,
+    aliases: Vec<String>, // lowercase names resolving to this species
+    min_weight: f64,      // live weight range in kilograms
+    max_weight: f64,
+    expected_dressing_percentage: f64,
+    allowed_product_types: Vec<String>, // empty allows any product type
+    min_temperature: f64,               // carcass acceptance range in degrees Celsius
+    max_temperature: f64,
+    min_ph: f64,
+    max_ph: f64,
+    shelf_life_days: u32,
+    updated_at: u64,
 }
 
+// Node of the managed expense category tree
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct MaintenancePayload {
-    slaughterhouse_id: u64,
-    equipment_name: String,
-    maintenance_type: String,
-    scheduled_date: u64,
-    estimated_cost: f64,
-    notes: String,
+struct ExpenseCategory {
+    id: u64,
+    code: String, // lowercase letters, digits and underscores
+    name: String,
+    parent_code: Option<String>, // none for top-level categories
+    updated_at: u64,
 }
 
+// Chain-of-custody step of a laboratory sample
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct ShipmentPayload {
-    slaughterhouse_id: u64,
-    product_ids: Vec<u64>,
-    destination: String,
-    expected_delivery: u64,
+struct CustodyEvent {
+    status: LabSampleStatus,
+    timestamp: u64,
+    handled_by: String,
 }
 
-#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
-enum Message {
-    Success(String),
-    Error(String),
-    NotFound(String),
-    InvalidPayload(String),
+// Regulatory laboratory sample taken from an animal or a meat product
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LabSample {
+    id: u64,
+    subject_type: LabSubjectType,
+    subject_id: u64,
+    slaughterhouse_id: u64,
+    test_type: LabTestType,
+    analyte: String,  // e.g., drug name, organism
+    limit_value: f64, // maximum acceptable result; 0 for presence/absence tests
+    unit: String,
+    lab_name: String,
+    status: LabSampleStatus,
+    custody_log: Vec<CustodyEvent>,
+    result_value: f64,
+    result: LabResult,
+    lab_reference: String,
+    resulted_at: u64,
 }
 
-// Implementing Storable for Slaughterhouse
-impl Storable for Slaughterhouse {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Acceptance limits for inspections of a species, optionally narrowed to a product type
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AcceptanceCriteria {
+    id: u64,
+    species: String,      // canonical species code
+    product_type: String, // empty for whole carcasses
+    min_temperature: f64,
+    max_temperature: f64,
+    min_ph: f64,
+    max_ph: f64,
+    updated_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// How the verdict of a quality inspection was reached
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectionVerdict {
+    inspection_id: u64,
+    criteria_source: String, // acceptance criteria id or species catalog
+    automatic_passed: bool,
+    failure_reasons: Vec<String>,
+    overridden: bool,
+    override_passed: bool,
+    override_reason: String,
+    override_by: String, // supervisor principal
+    overridden_at: u64,
 }
 
-impl BoundedStorable for Slaughterhouse {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Question of an inspection template
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct TemplateQuestion {
+    code: String, // stable across template versions
+    text: String,
+    answer_type: AnswerType,
+    required: bool,
+    expected_answer: bool, // passing answer of yes_no questions
+    min_value: f64,        // scale of score questions, acceptable range of numeric ones
+    max_value: f64,
 }
 
-// Implementing Storable for Animal
-impl Storable for Animal {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Versioned question set for one inspection type
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectionTemplate {
+    id: u64,
+    inspection_type: String,
+    version: u32,
+    name: String,
+    questions: Vec<TemplateQuestion>,
+    created_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectionAnswer {
+    question_code: String,
+    answer_type: AnswerType,
+    yes_no: bool,
+    value: f64,
+    photo_reference: String,
+    acceptable: bool,
 }
 
-impl BoundedStorable for Animal {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Structured answers of a quality inspection against a template version
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectionResponse {
+    inspection_id: u64,
+    template_id: u64,
+    inspection_type: String,
+    template_version: u32,
+    answers: Vec<InspectionAnswer>,
 }
 
-// Implementing Storable for MeatProduct
-impl Storable for MeatProduct {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// HACCP critical control point of a slaughterhouse with its critical limits
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CriticalControlPoint {
+    id: u64,
+    slaughterhouse_id: u64,
+    name: String,
+    hazard: String,
+    parameter: String, // e.g. carcass core temperature
+    unit: String,
+    min_limit: f64,
+    max_limit: f64,
+    monitoring_interval_minutes: u64,
+    created_at: u64,
+    updated_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CcpMonitoringLog {
+    id: u64,
+    ccp_id: u64,
+    slaughterhouse_id: u64,
+    value: f64,
+    within_limits: bool,
+    deviation_id: Option<u64>,
+    recorded_by: String,
+    recorded_at: u64,
+    notes: String,
 }
 
-impl BoundedStorable for MeatProduct {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Breach of a critical limit, open until a corrective action is recorded
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CcpDeviation {
+    id: u64,
+    ccp_id: u64,
+    slaughterhouse_id: u64,
+    log_id: u64,
+    value: f64,
+    limit_breached: String, // min or max
+    detected_at: u64,
+    status: DeviationStatus,
+    corrective_action_id: Option<u64>,
+    closed_at: u64,
 }
 
-// Implementing Storable for Expense
-impl Storable for Expense {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CorrectiveAction {
+    id: u64,
+    deviation_id: u64,
+    action_taken: String,
+    product_disposition: String, // what happened to product made during the deviation
+    preventive_measure: String,
+    performed_by: String,
+    recorded_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// Record a non-conformance report is raised against
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct NcrLink {
+    linked_type: NcrLinkType,
+    linked_id: u64,
 }
 
-impl BoundedStorable for Expense {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Non-conformance report, optionally linked to an inspection, shipment, maintenance record
+// or supplier; complaints and audit findings often have no record to link to
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct NonConformanceReport {
+    id: u64,
+    slaughterhouse_id: u64,
+    link: Option<NcrLink>,
+    severity: NcrSeverity,
+    description: String,
+    raised_by: String,
+    raised_at: u64,
+    root_cause: String,
+    containment_action: String,
+    status: NcrStatus,
+    last_verified_at: u64,
+    verified_by: String,
+    verification_notes: String,
+    effective: bool,
+    closed_at: u64,
 }
 
-// Implementing Storable for QualityInspection
-impl Storable for QualityInspection {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Corrective or preventive action of a non-conformance report
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CapaAction {
+    id: u64,
+    ncr_id: u64,
+    action_type: CapaActionType,
+    description: String,
+    owner_id: u64, // employee responsible for the action
+    due_date: u64,
+    created_at: u64,
+    status: CapaStatus,
+    completed_at: u64,
+    completion_notes: String,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// Certification held by an employee, valid until it expires
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct EmployeeCertification {
+    id: u64,
+    employee_id: u64,
+    certification_type: String, // e.g. meat_inspector
+    certificate_number: String,
+    issued_at: u64,
+    expires_at: u64,
 }
 
-impl BoundedStorable for QualityInspection {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Current pay rate of an employee
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PayRate {
+    employee_id: u64,
+    pay_type: PayType,
+    rate: f64,
+    updated_at: u64,
 }
 
-// Implementing Storable for Employee
-impl Storable for Employee {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Hours and heads worked by an employee on one day
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct TimesheetEntry {
+    id: u64,
+    employee_id: u64,
+    slaughterhouse_id: u64,
+    work_date: u64,
+    hours: f64,
+    heads: u32, // animals processed, paid to piece-rate employees
+    notes: String,
+    recorded_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// Payroll run of a slaughterhouse over the period [period_start, period_end)
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PayrollRun {
+    id: u64,
+    slaughterhouse_id: u64,
+    period_start: u64,
+    period_end: u64,
+    employee_count: u32,
+    total_gross_pay: f64,
+    run_by: String,
+    run_at: u64,
 }
 
-impl BoundedStorable for Employee {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Gross pay of one employee for a payroll run
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Payslip {
+    id: u64,
+    payroll_run_id: u64,
+    employee_id: u64,
+    slaughterhouse_id: u64,
+    period_start: u64,
+    period_end: u64,
+    pay_type: PayType,
+    rate: f64,
+    hours: f64,
+    heads: u64,
+    gross_pay: f64,
+
+    issued_at: u64,
 }
 
-// Implementing Storable for MaintenanceRecord
-impl Storable for MaintenanceRecord {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Stored file, uploaded in chunks and verified against its SHA-256 digest
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Blob {
+    id: u64,
+    file_name: String,
+    mime_type: String,
+    size: u64,
+    sha256: String, // lowercase hex digest declared when the upload started
+    bytes_received: u64,
+    chunk_count: u64,
+    status: BlobStatus,
+    uploaded_by: String,
+    created_at: u64,
+    completed_at: u64,
 }
 
-impl BoundedStorable for MaintenanceRecord {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Blob attached to a quality inspection, maintenance record, shipment or waste record
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Attachment {
+    id: u64,
+    blob_id: u64,
+    record_type: String,
+    record_id: u64,
+    description: String,
+    attached_by: String,
+    attached_at: u64,
 }
 
-// Implementing Storable for Supplier
-impl Storable for Supplier {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Measuring instrument of a slaughterhouse, valid until its calibration falls due
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Instrument {
+    id: u64,
+    slaughterhouse_id: u64,
+    instrument_type: InstrumentType,
+    serial_number: String,
+    model: String,
+    calibration_interval_days: u64,
+    last_calibrated_at: u64,
+    calibration_due_at: u64, // 0 until the first passed calibration
+    status: InstrumentStatus,
+    registered_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CalibrationRecord {
+    id: u64,
+    instrument_id: u64,
+    calibrated_at: u64,
+    performed_by: String,
+    certificate_number: String,
+    reference_value: f64,
+    measured_value: f64,
+    tolerance: f64,
+    passed: bool,
+    notes: String,
 }
 
-impl BoundedStorable for Supplier {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Reading taken for a record, with the instrument used and whether it is flagged
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InstrumentReading {
+    id: u64,
+    instrument_id: Option<u64>,
+    slaughterhouse_id: u64,
+    record_type: String, // quality_inspection, animal, meat_product or carcass_grade
+    record_id: u64,
+    reading_kind: String, // temperature, ph or weight
+    value: f64,
+    flagged: bool,
+    flag_reason: String,
+    recorded_at: u64,
 }
 
-// Implementing Storable for Shipment
-impl Storable for Shipment {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Licensed carrier allowed to collect the listed by-product categories
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WasteContractor {
+    id: u64,
+    name: String,
+    licence_number: String,
+    licence_expires_at: u64,
+    permitted_categories: Vec<AbpCategory>,
+    contact: String,
+    registered_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// Transfer manifest for one waste load, signed by the carrier on collection
+// and confirmed by the receiving facility on arrival
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WasteManifest {
+    id: u64,
+    slaughterhouse_id: u64,
+    contractor_id: u64,
+    waste_record_ids: Vec<u64>,
+    vehicle_plate: String,
+    carrier_signed_by: String,
+    receiving_facility: String,
+    declared_weight: f64, // sum of the waste record quantities, in kilograms
+    gross_weight: f64,    // weighbridge readings at dispatch
+    tare_weight: f64,
+    net_weight: f64,
+    dispatched_at: u64,
+    received_weight: f64,
+    confirmed_by: String,
+    confirmed_at: u64, // 0 until the receiving facility confirms
 }
 
-impl BoundedStorable for Shipment {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Cumulative register reading of a utility meter; consumption is the rise between readings
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MeterReading {
+    id: u64,
+    slaughterhouse_id: u64,
+    meter_id: String, // serial or label of the meter on site
+    meter_type: MeterType,
+    reading: f64,
+    read_at: u64,
+    recorded_by: String,
 }
 
-// Implementing Storable for WasteRecord
-impl Storable for WasteRecord {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// Effluent limits of a slaughterhouse's discharge permit, in mg/L
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct DischargePermit {
+    slaughterhouse_id: u64,
+    permit_number: String,
+    bod_limit: f64,
+    cod_limit: f64,
+    tss_limit: f64,
+    updated_at: u64,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// Effluent lab result checked against the discharge permit in force when recorded
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct EffluentResult {
+    id: u64,
+    slaughterhouse_id: u64,
+    permit_number: String,
+    sampled_at: u64,
+    lab_reference: String,
+    bod: f64, // biochemical oxygen demand, mg/L
+    cod: f64, // chemical oxygen demand, mg/L
+    tss: f64, // total suspended solids, mg/L
+    exceedances: Vec<String>,
+    compliant: bool,
+    recorded_at: u64,
 }
 
-impl BoundedStorable for WasteRecord {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Stored status or type that did not match any enum value during migration
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct StatusMigrationIssue {
+    id: u64,
+    entity: String,
+    record_id: u64,
+    field: String,
+    original_value: String,
+    assigned_value: String,
 }
 
-// Implementing Storable for FinancialMetrics
-impl Storable for FinancialMetrics {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct FinancialMetrics {
+    total_revenue: f64,
+    total_expenses: f64,
+    profit_margin: f64,
+    operating_costs: f64,
+    maintenance_costs: f64,
+    labor_costs: f64,
+    waste_management_costs: f64,
+    revenue_by_product: HashMap<String, f64>,
+    expenses_by_category: HashMap<String, f64>,
+    animals_processed: u32,
+    labor_cost_per_head: f64,
+    cost_per_head: f64, // expenses, maintenance, waste and labor costs
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct QualityMetrics {
+    total_inspections: u32,
+    passed_inspections: u32,
+    failure_rate: f64,
+    average_temperature: f64,
+    average_ph_level: f64,
+    inspections: Vec<QualityInspection>,
 }
 
-impl BoundedStorable for FinancialMetrics {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MaintenanceAnalytics {
+    total_maintenance_cost: f64,
+    maintenance_by_type: HashMap<String, u32>,
+    equipment_reliability: HashMap<String, f64>,
+    pending_maintenance: Vec<MaintenanceRecord>,
+    equipment_history: HashMap<String, Vec<MaintenanceRecord>>,
 }
 
-// Memory management
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InventoryAnalytics {
+    product_counts: HashMap<String, u32>,
+    total_inventory_value: f64,
+    products_by_status: HashMap<String, Vec<MeatProduct>>,
+    low_stock_items: Vec<MeatProduct>,
+    expired_items: Vec<MeatProduct>, // in stock beyond the species shelf life
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WelfareKpis {
+    animals_received: u32,
+    animals_stunned: u32,
+    re_stun_events: u32,
+    re_stun_rate: f64,
+    average_lairage_hours: f64,
+    average_transport_hours: f64,
+    dead_on_arrival: u32,
+    mortality_on_arrival_rate: f64,
+    flagged_events: Vec<WelfareEvent>,
+    lairage_overruns: Vec<u64>, // animals currently in lairage beyond the limit
+}
+
+// Resource use over a period, per animal processed and per kg of product
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct EnvironmentalAnalytics {
+    animals_processed: u32,
+    product_weight: f64, // kg of product created in the period
+    water_litres: f64,
+    energy_kwh: f64, // electricity and gas
+    water_litres_per_head: f64,
+    water_litres_per_kg: f64,
+    energy_kwh_per_head: f64,
+    energy_kwh_per_kg: f64,
+    waste_kg: f64,
+    waste_kg_per_head: f64,
+    waste_kg_by_category: HashMap<String, f64>,
+    emissions_kg_co2e: f64,
+    emissions_kg_co2e_per_head: f64,
+    effluent_results: u32,
+    effluent_exceedances: u32,
+}
+
+// Costs rolled up to one category of the expense tree
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ExpenseCategoryTotal {
+    code: String,
+    name: String,
+    parent_code: Option<String>,
+    depth: u32,
+    total: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LockdownExposure {
+    lockdown: Lockdown,
+    quarantined_animals: Vec<Animal>,
+    quarantined_products: Vec<MeatProduct>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct TagValidation {
+    tag_number: String, // normalized form stored on the animal
+    scheme: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ConsignmentDiscrepancies {
+    consignment: Consignment,
+    missing_animals: u32,
+    surplus_animals: u32,
+    dead_on_arrival: Vec<u64>,
+    transit_hours: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct GradeDistribution {
+    total_graded: u32,
+    average_carcass_weight: f64,
+    grades: HashMap<String, u32>,
+    grades_by_supplier: HashMap<u64, HashMap<String, u32>>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SettlementDetails {
+    statement: SettlementStatement,
+    lines: Vec<SettlementLine>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct HeldProduct {
+    product: MeatProduct,
+    sample_id: u64,
+    reason: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct QuestionAggregate {
+    question_code: String,
+    text: String,
+    answer_type: AnswerType,
+    responses: u64,
+    failed: u64,
+    yes_count: u64,
+    no_count: u64,
+    average_value: f64,
+    min_value: f64,
+    max_value: f64,
+    photo_count: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CcpStatus {
+    ccp: CriticalControlPoint,
+    last_monitored_at: u64,
+    next_due_at: u64,
+    overdue: bool,
+    open_deviations: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PayrollRunDetails {
+    run: PayrollRun,
+    payslips: Vec<Payslip>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct NcrDetails {
+    report: NonConformanceReport,
+    actions: Vec<CapaAction>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SummaryStatistics {
+    count: u64,
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+    p5: f64,
+    median: f64,
+    p95: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct DailyStatistics {
+    day_start: u64,
+    statistics: SummaryStatistics,
+    range: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct GroupStatistics {
+    group: String,
+    statistics: SummaryStatistics,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ControlRuleViolation {
+    rule: u8, // Western Electric rule number
+    day_start: u64,
+    description: String,
+}
+
+// Statistical process control of one inspection measurement, subgrouped per day
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SpcReport {
+    parameter: String, // temperature or ph
+    overall: SummaryStatistics,
+    daily: Vec<DailyStatistics>,
+    subgroup_size: u64,
+    center_line: f64,
+    upper_control_limit: f64,
+    lower_control_limit: f64,
+    average_range: f64,
+    range_upper_control_limit: f64,
+    range_lower_control_limit: f64,
+    violations: Vec<ControlRuleViolation>,
+    trend: String,    // rising, falling or stable
+    trend_slope: f64, // change of the daily mean per day
+    by_species: Vec<GroupStatistics>,
+    by_inspector: Vec<GroupStatistics>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectorAnalytics {
+    employee_id: u64,
+    name: String,
+    total_inspections: u32,
+    failed_inspections: u32,
+    failure_rate: f64,
+    overridden_verdicts: u32,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct HttpResponse {
+    status_code: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    streaming_strategy: Option<StreamingStrategy>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+enum StreamingStrategy {
+    Callback {
+        callback: candid::Func,
+        token: StreamingCallbackToken,
+    },
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct StreamingCallbackToken {
+    blob_id: u64,
+    index: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct StreamingCallbackHttpResponse {
+    body: Vec<u8>,
+    token: Option<StreamingCallbackToken>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InstrumentDetails {
+    instrument: Instrument,
+    in_calibration: bool,
+    calibrations: Vec<CalibrationRecord>,
+}
+
+// Payload structs
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CreateSlaughterhousePayload {
+    name: String,
+    location: String,
+    contact: String,
+    email: String,
+    capacity: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RegisterAnimalPayload {
+    slaughterhouse_id: u64,
+    tag_number: String,
+    species: String,
+    weight: f64,
+    scale_id: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CreateMeatProductPayload {
+    animal_id: u64,
+    slaughterhouse_id: u64,
+    product_type: String,
+    weight: f64,
+    price_per_kg: f64,
+    scale_id: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ExpenseCategoryPayload {
+    code: String,
+    name: String,
+    parent_code: Option<String>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RecordExpensePayload {
+    slaughterhouse_id: u64,
+    category: String,
+    amount: f64,
+    description: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectionAnswerPayload {
+    question_code: String,
+    yes_no: Option<bool>,
+    value: Option<f64>,
+    photo_reference: Option<String>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct QualityInspectionPayload {
+    animal_id: u64,
+    product_type: String, // empty for whole carcasses
+    temperature: f64,
+    ph_level: f64,
+    thermometer_id: u64,
+    ph_meter_id: u64,
+    visual_inspection: String,
+    template_id: Option<u64>, // latest version of an inspection template
+    answers: Vec<InspectionAnswerPayload>,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct EmployeePayload {
+    slaughterhouse_id: u64,
+    name: String,
+    role: String,
+    certification: String,
+    contact: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MaintenancePayload {
+    slaughterhouse_id: u64,
+    equipment_name: String,
+    maintenance_type: MaintenanceType,
+    scheduled_date: u64,
+    estimated_cost: f64,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WasteDisposalPayload {
+    slaughterhouse_id: u64,
+    category: AbpCategory,
+    waste_type: String,
+    quantity: f64,
+    disposal_method: String,
+    cost: f64,
+    handled_by: String,
+    source_animal_id: Option<u64>,
+    source_product_id: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ShipmentPayload {
+    slaughterhouse_id: u64,
+    product_ids: Vec<u64>,
+    destination: String,
+    expected_delivery: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct VeterinaryTreatmentPayload {
+    animal_id: u64,
+    drug_name: String,
+    dose: f64,
+    dose_unit: String,
+    administration_date: u64,
+    withdrawal_days: u32,
+    declared_by: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WelfareEventPayload {
+    animal_id: u64,
+    event_type: WelfareEventType,
+    duration_hours: f64,
+    effective: bool,
+    recorded_by: String,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WelfareLimitsPayload {
+    slaughterhouse_id: u64,
+    max_transport_hours: f64,
+    max_lairage_hours: f64,
+    feed_required_after_hours: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LockdownPayload {
+    slaughterhouse_id: u64,
+    disease: String,
+    suspected_animal_id: u64,
+    exposure_cutoff: u64,
+    reason: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SupplierPayload {
+    name: String,
+    contact: String,
+    email: String,
+    supplier_type: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ConsignmentPayload {
+    slaughterhouse_id: u64,
+    supplier_id: u64,
+    haulier: String,
+    vehicle_plate: String,
+    movement_permit_number: String,
+    departure_time: u64,
+    arrival_time: u64,
+    headcount_declared: u32,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ConsignmentAnimalPayload {
+    tag_number: String,
+    species: String,
+    weight: f64,
+    scale_id: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RegisterConsignmentAnimalsPayload {
+    consignment_id: u64,
+    animals: Vec<ConsignmentAnimalPayload>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CarcassGradePayload {
+    animal_id: u64,
+    scheme: String,
+    carcass_weight: f64,
+    conformation: String,
+    fat_class: String,
+    quality_grade: String,
+    yield_grade: String,
+    lean_meat_percentage: f64,
+    graded_by: String,
+    scale_id: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PriceGridPayload {
+    species: String,
+    scheme: String,
+    valid_from: u64,
+    valid_to: u64,
+    base_price_per_kg: f64,
+    adjustments: Vec<GradeAdjustment>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SettlementPayload {
+    consignment_id: u64,
+    deductions: Vec<SettlementDeduction>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SpeciesPayload {
+    code: String,
+    name: String,
+    aliases: Vec<String>,
+    min_weight: f64,
+    max_weight: f64,
+    expected_dressing_percentage: f64,
+    allowed_product_types: Vec<String>,
+    min_temperature: f64,
+    max_temperature: f64,
+    min_ph: f64,
+    max_ph: f64,
+    shelf_life_days: u32,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LabSamplePayload {
+    subject_type: LabSubjectType,
+    subject_id: u64,
+    test_type: LabTestType,
+    analyte: String,
+    limit_value: f64,
+    unit: String,
+    taken_by: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LabResultPayload {
+    sample_id: u64,
+    result_value: f64,
+    lab_reference: String,
+    analyst: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AcceptanceCriteriaPayload {
+    species: String,
+    product_type: String,
+    min_temperature: f64,
+    max_temperature: f64,
+    min_ph: f64,
+    max_ph: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct VerdictOverridePayload {
+    inspection_id: u64,
+    passed: bool,
+    reason: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InspectionTemplatePayload {
+    inspection_type: String,
+    name: String,
+    questions: Vec<TemplateQuestion>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CriticalControlPointPayload {
+    slaughterhouse_id: u64,
+    name: String,
+    hazard: String,
+    parameter: String,
+    unit: String,
+    min_limit: f64,
+    max_limit: f64,
+    monitoring_interval_minutes: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CcpMonitoringPayload {
+    ccp_id: u64,
+    value: f64,
+    recorded_by: String,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CorrectiveActionPayload {
+    deviation_id: u64,
+    action_taken: String,
+    product_disposition: String,
+    preventive_measure: String,
+    performed_by: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct NonConformancePayload {
+    slaughterhouse_id: u64,
+    link: Option<NcrLink>,
+    severity: NcrSeverity,
+    description: String,
+    raised_by: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct NcrInvestigationPayload {
+    ncr_id: u64,
+    root_cause: String,
+    containment_action: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CapaActionPayload {
+    ncr_id: u64,
+    action_type: CapaActionType,
+    description: String,
+    owner_id: u64,
+    due_date: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct NcrVerificationPayload {
+    ncr_id: u64,
+    verified_by: String,
+    effective: bool,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SpcQueryPayload {
+    slaughterhouse_id: u64,
+    start_date: u64,
+    end_date: u64,
+    parameter: String,
+    species: Option<String>,
+    inspector_id: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PayRatePayload {
+    employee_id: u64,
+    pay_type: PayType,
+    rate: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct TimesheetEntryPayload {
+    employee_id: u64,
+    work_date: u64,
+    hours: f64,
+    heads: u32,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PayrollRunPayload {
+    slaughterhouse_id: u64,
+    period_start: u64,
+    period_end: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CertificationPayload {
+    employee_id: u64,
+    certification_type: String,
+    certificate_number: String,
+    issued_at: u64,
+    expires_at: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UploadSessionPayload {
+    file_name: String,
+    mime_type: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AttachmentPayload {
+    blob_id: u64,
+    record_type: String,
+    record_id: u64,
+    description: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct InstrumentPayload {
+    slaughterhouse_id: u64,
+    instrument_type: InstrumentType,
+    serial_number: String,
+    model: String,
+    calibration_interval_days: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WasteContractorPayload {
+    name: String,
+    licence_number: String,
+    licence_expires_at: u64,
+    permitted_categories: Vec<AbpCategory>,
+    contact: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WasteManifestPayload {
+    slaughterhouse_id: u64,
+    contractor_id: u64,
+    waste_record_ids: Vec<u64>,
+    vehicle_plate: String,
+    carrier_signed_by: String,
+    receiving_facility: String,
+    gross_weight: f64,
+    tare_weight: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ManifestConfirmationPayload {
+    manifest_id: u64,
+    received_weight: f64,
+    confirmed_by: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MeterReadingPayload {
+    slaughterhouse_id: u64,
+    meter_id: String,
+    meter_type: MeterType,
+
+    reading: f64,
+    read_at: u64,
+    recorded_by: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct DischargePermitPayload {
+    slaughterhouse_id: u64,
+    permit_number: String,
+    bod_limit: f64,
+    cod_limit: f64,
+    tss_limit: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct EffluentResultPayload {
+    slaughterhouse_id: u64,
+    sampled_at: u64,
+    lab_reference: String,
+    bod: f64,
+    cod: f64,
+    tss: f64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CalibrationPayload {
+    instrument_id: u64,
+    performed_by: String,
+    certificate_number: String,
+    reference_value: f64,
+    measured_value: f64,
+    tolerance: f64,
+    notes: String,
+}
+
+// Stable error codes for clients to branch on; the text message may change
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum ErrorCode {
+    #[default]
+    NotFound,
+    ValidationFailed,
+    AlreadyExists,
+    InvalidState,
+    Unauthorized,
+    Blocked,
+}
+
+// Reason a single payload field was rejected
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+enum FieldErrorCode {
+    #[default]
+    Required,
+    TooLong,
+    TooMany,
+    OutOfRange,
+    InvalidFormat,
+    InvalidValue,
+    Duplicate,
+    NotFinite,
+    UnknownReference,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct FieldError {
+    field: String,
+    code: FieldErrorCode,
+    message: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ErrorDetail {
+    code: ErrorCode,
+    message: String,
+    entity_type: Option<String>,
+    entity_id: Option<u64>,
+    field_errors: Vec<FieldError>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+enum Message {
+    Success(String),
+    Error(ErrorDetail),
+    NotFound(ErrorDetail),
+    InvalidPayload(ErrorDetail),
+}
+
+impl Message {
+    // Missing record identified by id, e.g. not_found("animal", 7)
+    fn not_found(entity_type: &str, entity_id: u64) -> Self {
+        Message::NotFound(ErrorDetail {
+            code: ErrorCode::NotFound,
+            message: format!("{} {} not found", entity_type.replace('_', " "), entity_id),
+            entity_type: Some(entity_type.to_string()),
+            entity_id: Some(entity_id),
+            field_errors: Vec::new(),
+        })
+    }
+
+    // Missing record looked up by something other than its id
+    fn not_found_by(entity_type: &str, message: impl Into<String>) -> Self {
+        Message::NotFound(ErrorDetail {
+            code: ErrorCode::NotFound,
+            message: message.into(),
+            entity_type: Some(entity_type.to_string()),
+            ..Default::default()
+        })
+    }
+
+    // Request that is well formed but not allowed in the current state
+    fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Message::Error(ErrorDetail {
+            code,
+            message: message.into(),
+            ..Default::default()
+        })
+    }
+
+    // Payload rejected because of a single field
+    fn invalid(field: &str, code: FieldErrorCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Message::InvalidPayload(ErrorDetail {
+            code: ErrorCode::ValidationFailed,
+            message: message.clone(),
+            field_errors: vec![FieldError {
+                field: field.to_string(),
+                code,
+                message,
+            }],
+            ..Default::default()
+        })
+    }
+
+    // Names the record the error is about
+    fn on(self, entity_type: &str, entity_id: u64) -> Self {
+        let attach = |mut detail: ErrorDetail| {
+            detail.entity_type = Some(entity_type.to_string());
+            detail.entity_id = Some(entity_id);
+            detail
+        };
+        match self {
+            Message::Success(text) => Message::Success(text),
+            Message::Error(detail) => Message::Error(attach(detail)),
+            Message::NotFound(detail) => Message::NotFound(attach(detail)),
+            Message::InvalidPayload(detail) => Message::InvalidPayload(attach(detail)),
+        }
+    }
+}
+
+// Existence check behind Validator::reference
+fn record_exists(entity_type: &str, id: u64) -> bool {
+    match entity_type {
+        "slaughterhouse" => SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&id)),
+        "animal" => ANIMALS.with(|animals| animals.borrow().contains_key(&id)),
+        "product" => MEAT_PRODUCTS.with(|products| products.borrow().contains_key(&id)),
+        "employee" => EMPLOYEES.with(|employees| employees.borrow().contains_key(&id)),
+        "supplier" => SUPPLIERS.with(|suppliers| suppliers.borrow().contains_key(&id)),
+        "instrument" => INSTRUMENTS.with(|instruments| instruments.borrow().contains_key(&id)),
+        "waste_contractor" => {
+            WASTE_CONTRACTORS.with(|contractors| contractors.borrow().contains_key(&id))
+        }
+        "inspection_template" => {
+            INSPECTION_TEMPLATES.with(|templates| templates.borrow().contains_key(&id))
+        }
+        _ => false,
+    }
+}
+
+// Collects every invalid field of a payload so they are reported together
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn new() -> Self {
+        Validator::default()
+    }
+
+    fn required(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(
+            !value.trim().is_empty(),
+            field,
+            FieldErrorCode::Required,
+            format!("{} is required", field),
+        )
+    }
+
+    // Optional text within the length limit, counted in UTF-8 bytes since the limits
+    // keep records within their storage size
+    fn max_length(&mut self, field: &str, value: &str, max_length: usize) -> &mut Self {
+        self.check(
+            value.len() <= max_length,
+            field,
+            FieldErrorCode::TooLong,
+            format!("{} is limited to {} bytes", field, max_length),
+        )
+    }
+
+    // Required text within the length limit
+    fn text(&mut self, field: &str, value: &str, max_length: usize) -> &mut Self {
+        self.required(field, value)
+            .max_length(field, value, max_length)
+    }
+
+    fn max_count(&mut self, field: &str, count: usize, max_count: usize) -> &mut Self {
+        self.check(
+            count <= max_count,
+            field,
+            FieldErrorCode::TooMany,
+            format!("{} is limited to {} entries", field, max_count),
+        )
+    }
+
+    // Finite number within the inclusive range; NaN and infinities are reported separately
+    fn range(&mut self, field: &str, value: f64, min: f64, max: f64) -> &mut Self {
+        self.finite(field, value, (min..=max).contains(&value), || {
+            format!("{} must be between {} and {}", field, min, max)
+        })
+    }
+
+    fn positive(&mut self, field: &str, value: f64) -> &mut Self {
+        self.finite(field, value, value > 0.0, || {
+            format!("{} must be positive", field)
+        })
+    }
+
+    fn non_negative(&mut self, field: &str, value: f64) -> &mut Self {
+        self.finite(field, value, value >= 0.0, || {
+            format!("{} cannot be negative", field)
+        })
+    }
+
+    // NaN and infinities fail before the range condition is considered
+    fn finite(
+        &mut self,
+        field: &str,
+        value: f64,
+        in_range: bool,
+        message: impl FnOnce() -> String,
+    ) -> &mut Self {
+        if !value.is_finite() {
+            return self.check(
+                false,
+                field,
+                FieldErrorCode::NotFinite,
+                format!("{} must be a finite number", field),
+            );
+        }
+        self.check(in_range, field, FieldErrorCode::OutOfRange, message())
+    }
+
+    // min_<field> and max_<field> pair, each within the limits and in order
+    fn bounds(&mut self, field: &str, min: f64, max: f64, limits: (f64, f64)) -> &mut Self {
+        let min_field = format!("min_{}", field);
+        let max_field = format!("max_{}", field);
+        self.range(&min_field, min, limits.0, limits.1)
+            .range(&max_field, max, limits.0, limits.1)
+            .check(
+                min < max,
+                &max_field,
+                FieldErrorCode::OutOfRange,
+                format!("{} must exceed {}", max_field, min_field),
+            )
+    }
+
+    // Referential integrity: the referenced record must exist
+    fn reference(&mut self, field: &str, entity_type: &str, id: u64) -> &mut Self {
+        self.check(
+            record_exists(entity_type, id),
+            field,
+            FieldErrorCode::UnknownReference,
+            format!("{} {} does not exist", entity_type.replace('_', " "), id),
+        )
+    }
+
+    // Records a field error unless the condition holds
+    fn check(
+        &mut self,
+        condition: bool,
+        field: &str,
+        code: FieldErrorCode,
+        message: impl Into<String>,
+    ) -> &mut Self {
+        if !condition {
+            self.errors.push(FieldError {
+                field: field.to_string(),
+                code,
+                message: message.into(),
+            });
+        }
+        self
+    }
+
+    fn finish(&mut self) -> Result<(), Message> {
+        match self.errors.len() {
+            0 => Ok(()),
+            1 => {
+                let error = self.errors.remove(0);
+                Err(Message::invalid(&error.field, error.code, error.message))
+            }
+            count => Err(Message::InvalidPayload(ErrorDetail {
+                code: ErrorCode::ValidationFailed,
+                message: format!("{} fields are invalid", count),
+                field_errors: std::mem::take(&mut self.errors),
+                ..Default::default()
+            })),
+        }
+    }
+}
+
+// Normalized ear tag, used as key of the tag index
+#[derive(
+    candid::CandidType, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord,
+)]
+struct TagNumber(String);
+
+// Implementing Storable for TagNumber
+impl Storable for TagNumber {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for TagNumber {
+    const MAX_SIZE: u32 = 64;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Principal in text form, used as key of the employee principal index
+#[derive(
+    candid::CandidType, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord,
+)]
+struct PrincipalKey(String);
+
+// Implementing Storable for PrincipalKey
+impl Storable for PrincipalKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for PrincipalKey {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Raw bytes of one chunk of a blob
+struct BlobChunk(Vec<u8>);
+
+// Implementing Storable for BlobChunk
+impl Storable for BlobChunk {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+}
+
+impl BoundedStorable for BlobChunk {
+    const MAX_SIZE: u32 = MAX_CHUNK_SIZE as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Slaughterhouse
+impl Storable for Slaughterhouse {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Slaughterhouse {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Animal
+impl Storable for Animal {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Animal {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for MeatProduct
+impl Storable for MeatProduct {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for MeatProduct {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Expense
+impl Storable for Expense {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Expense {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for QualityInspection
+impl Storable for QualityInspection {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for QualityInspection {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Employee
+impl Storable for Employee {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Employee {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for MaintenanceRecord
+impl Storable for MaintenanceRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for MaintenanceRecord {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Supplier
+impl Storable for Supplier {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Supplier {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Shipment
+impl Storable for Shipment {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Shipment {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for WasteRecord
+impl Storable for WasteRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for WasteRecord {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for FinancialMetrics
+impl Storable for FinancialMetrics {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for FinancialMetrics {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for VeterinaryTreatment
+impl Storable for VeterinaryTreatment {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for VeterinaryTreatment {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for WelfareEvent
+impl Storable for WelfareEvent {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for WelfareEvent {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for WelfareLimits
+impl Storable for WelfareLimits {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for WelfareLimits {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Lockdown
+impl Storable for Lockdown {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Lockdown {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Consignment
+impl Storable for Consignment {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Consignment {
+    const MAX_SIZE: u32 = 8192; // room for MAX_CONSIGNMENT_ANIMALS animal ids
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CarcassGrade
+impl Storable for CarcassGrade {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CarcassGrade {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for PriceGrid
+impl Storable for PriceGrid {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for PriceGrid {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for SettlementStatement
+impl Storable for SettlementStatement {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for SettlementStatement {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for SettlementLine
+impl Storable for SettlementLine {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for SettlementLine {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Species
+impl Storable for Species {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Species {
+    // room for full alias and product type lists at MAX_NAME_LENGTH
+    const MAX_SIZE: u32 = 4096;
+
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for PayRate
+impl Storable for PayRate {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for PayRate {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for TimesheetEntry
+impl Storable for TimesheetEntry {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for TimesheetEntry {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for PayrollRun
+impl Storable for PayrollRun {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for PayrollRun {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Payslip
+impl Storable for Payslip {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Payslip {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for ExpenseCategory
+impl Storable for ExpenseCategory {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for ExpenseCategory {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for LabSample
+impl Storable for LabSample {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LabSample {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for AcceptanceCriteria
+impl Storable for AcceptanceCriteria {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for AcceptanceCriteria {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for InspectionVerdict
+impl Storable for InspectionVerdict {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for InspectionVerdict {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for InspectionTemplate
+impl Storable for InspectionTemplate {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for InspectionTemplate {
+    const MAX_SIZE: u32 = 16384;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for InspectionResponse
+impl Storable for InspectionResponse {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for InspectionResponse {
+    const MAX_SIZE: u32 = 16384;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CriticalControlPoint
+impl Storable for CriticalControlPoint {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CriticalControlPoint {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CcpMonitoringLog
+impl Storable for CcpMonitoringLog {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CcpMonitoringLog {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CcpDeviation
+impl Storable for CcpDeviation {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CcpDeviation {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CorrectiveAction
+impl Storable for CorrectiveAction {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CorrectiveAction {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for NonConformanceReport
+impl Storable for NonConformanceReport {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for NonConformanceReport {
+    const MAX_SIZE: u32 = 8192;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CapaAction
+impl Storable for CapaAction {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CapaAction {
+    const MAX_SIZE: u32 = 4096; // room for a description and completion notes at MAX_NCR_TEXT_LENGTH
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for EmployeeCertification
+impl Storable for EmployeeCertification {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for EmployeeCertification {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Blob
+impl Storable for Blob {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Blob {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Attachment
+impl Storable for Attachment {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Attachment {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for Instrument
+impl Storable for Instrument {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Instrument {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for CalibrationRecord
+impl Storable for CalibrationRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for CalibrationRecord {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for InstrumentReading
+impl Storable for InstrumentReading {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for InstrumentReading {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for WasteContractor
+impl Storable for WasteContractor {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for WasteContractor {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for WasteManifest
+impl Storable for WasteManifest {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for WasteManifest {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for MeterReading
+impl Storable for MeterReading {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for MeterReading {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for DischargePermit
+impl Storable for DischargePermit {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for DischargePermit {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for EffluentResult
+impl Storable for EffluentResult {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for EffluentResult {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for StatusMigrationIssue
+impl Storable for StatusMigrationIssue {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for StatusMigrationIssue {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for LegacyAnimal
+impl Storable for LegacyAnimal {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LegacyAnimal {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for LegacyMeatProduct
+impl Storable for LegacyMeatProduct {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LegacyMeatProduct {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for LegacyEmployee
+impl Storable for LegacyEmployee {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LegacyEmployee {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for LegacyMaintenanceRecord
+impl Storable for LegacyMaintenanceRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LegacyMaintenanceRecord {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Implementing Storable for LegacyShipment
+impl Storable for LegacyShipment {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LegacyShipment {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Memory management
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
         MemoryManager::init(DefaultMemoryImpl::default())
     );
 
-    static ID_COUNTER: RefCell<IdCell> = RefCell::new(
-        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0)
-            .expect("Cannot create a counter")
-    );
+    static ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static SLAUGHTERHOUSES: RefCell<StableBTreeMap<u64, Slaughterhouse, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+        ));
+
+    static ANIMALS: RefCell<StableBTreeMap<u64, Animal, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
+        ));
+
+    static MEAT_PRODUCTS: RefCell<StableBTreeMap<u64, MeatProduct, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
+        ));
+
+    static EXPENSES: RefCell<StableBTreeMap<u64, Expense, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
+        ));
+
+    static QUALITY_INSPECTIONS: RefCell<StableBTreeMap<u64, QualityInspection, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
+        ));
+
+    static EMPLOYEES: RefCell<StableBTreeMap<u64, Employee, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
+        ));
+
+    static MAINTENANCE_RECORDS: RefCell<StableBTreeMap<u64, MaintenanceRecord, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
+        ));
+
+    static SUPPLIERS: RefCell<StableBTreeMap<u64, Supplier, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17)))
+        ));
+
+    static SHIPMENTS: RefCell<StableBTreeMap<u64, Shipment, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
+        ));
+
+    static WASTE_RECORDS: RefCell<StableBTreeMap<u64, WasteRecord, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
+        ));
+
+    static FINANCIAL_METRICS: RefCell<StableBTreeMap<u64, FinancialMetrics, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))
+        ));
+
+    static VETERINARY_TREATMENTS: RefCell<StableBTreeMap<u64, VeterinaryTreatment, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21)))
+        ));
+
+    static WELFARE_EVENTS: RefCell<StableBTreeMap<u64, WelfareEvent, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))
+        ));
+
+    static WELFARE_LIMITS: RefCell<StableBTreeMap<u64, WelfareLimits, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23)))
+        ));
+
+    static LOCKDOWNS: RefCell<StableBTreeMap<u64, Lockdown, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24)))
+        ));
+
+    // Unique index of ear tags across all slaughterhouses
+    static TAG_INDEX: RefCell<StableBTreeMap<TagNumber, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25)))
+        ));
+
+    static CONSIGNMENTS: RefCell<StableBTreeMap<u64, Consignment, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26)))
+        ));
+
+    static CARCASS_GRADES: RefCell<StableBTreeMap<u64, CarcassGrade, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27)))
+        ));
+
+    static PRICE_GRIDS: RefCell<StableBTreeMap<u64, PriceGrid, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28)))
+        ));
+
+    static SETTLEMENTS: RefCell<StableBTreeMap<u64, SettlementStatement, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29)))
+        ));
+
+    static SETTLEMENT_LINES: RefCell<StableBTreeMap<u64, SettlementLine, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30)))
+        ));
+
+    static SPECIES_CATALOG: RefCell<StableBTreeMap<u64, Species, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31)))
+        ));
+
+    static LAB_SAMPLES: RefCell<StableBTreeMap<u64, LabSample, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(32)))
+        ));
+
+    static ACCEPTANCE_CRITERIA: RefCell<StableBTreeMap<u64, AcceptanceCriteria, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(33)))
+        ));
+
+    // Keyed by inspection id
+    static INSPECTION_VERDICTS: RefCell<StableBTreeMap<u64, InspectionVerdict, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(34)))
+        ));
+
+    static INSPECTION_TEMPLATES: RefCell<StableBTreeMap<u64, InspectionTemplate, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(35)))
+        ));
+
+    // Keyed by inspection id
+    static INSPECTION_RESPONSES: RefCell<StableBTreeMap<u64, InspectionResponse, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(36)))
+        ));
+
+    static CRITICAL_CONTROL_POINTS: RefCell<StableBTreeMap<u64, CriticalControlPoint, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(37)))
+        ));
+
+    static CCP_MONITORING_LOGS: RefCell<StableBTreeMap<u64, CcpMonitoringLog, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(38)))
+        ));
+
+    static CCP_DEVIATIONS: RefCell<StableBTreeMap<u64, CcpDeviation, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(39)))
+        ));
+
+    static CORRECTIVE_ACTIONS: RefCell<StableBTreeMap<u64, CorrectiveAction, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(40)))
+        ));
+
+    static NON_CONFORMANCES: RefCell<StableBTreeMap<u64, NonConformanceReport, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(41)))
+        ));
+
+    static CAPA_ACTIONS: RefCell<StableBTreeMap<u64, CapaAction, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(42)))
+        ));
+
+    // Keyed by principal text
+    static EMPLOYEE_PRINCIPALS: RefCell<StableBTreeMap<PrincipalKey, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(43)))
+        ));
+
+    static EMPLOYEE_CERTIFICATIONS: RefCell<StableBTreeMap<u64, EmployeeCertification, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(44)))
+        ));
+
+    static BLOBS: RefCell<StableBTreeMap<u64, Blob, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(45)))
+        ));
+
+    // Keyed by blob id and chunk index
+    static BLOB_CHUNKS: RefCell<StableBTreeMap<(u64, u64), BlobChunk, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(46)))
+        ));
+
+    static ATTACHMENTS: RefCell<StableBTreeMap<u64, Attachment, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(47)))
+        ));
+
+    static INSTRUMENTS: RefCell<StableBTreeMap<u64, Instrument, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(48)))
+        ));
+
+    static CALIBRATION_RECORDS: RefCell<StableBTreeMap<u64, CalibrationRecord, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(49)))
+        ));
+
+    static INSTRUMENT_READINGS: RefCell<StableBTreeMap<u64, InstrumentReading, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(50)))
+        ));
+
+    static STORED_SCHEMA_VERSION: RefCell<Cell<u64, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(51))), 0)
+            .expect("Cannot create the schema version")
+    );
+
+    static STATUS_MIGRATION_ISSUES: RefCell<StableBTreeMap<u64, StatusMigrationIssue, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(52)))
+        ));
+
+    static WASTE_CONTRACTORS: RefCell<StableBTreeMap<u64, WasteContractor, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(53)))
+        ));
+
+    static WASTE_MANIFESTS: RefCell<StableBTreeMap<u64, WasteManifest, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(54)))
+        ));
+
+    static METER_READINGS: RefCell<StableBTreeMap<u64, MeterReading, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(55)))
+        ));
+
+    static DISCHARGE_PERMITS: RefCell<StableBTreeMap<u64, DischargePermit, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(56)))
+        ));
+
+    static EFFLUENT_RESULTS: RefCell<StableBTreeMap<u64, EffluentResult, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(57)))
+        ));
+
+    static EXPENSE_CATEGORIES: RefCell<StableBTreeMap<u64, ExpenseCategory, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(58)))
+        ));
+
+    static PAY_RATES: RefCell<StableBTreeMap<u64, PayRate, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(59)))
+        ));
+
+    static TIMESHEET_ENTRIES: RefCell<StableBTreeMap<u64, TimesheetEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(60)))
+        ));
+
+    static PAYROLL_RUNS: RefCell<StableBTreeMap<u64, PayrollRun, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(61)))
+        ));
+
+    static PAYSLIPS: RefCell<StableBTreeMap<u64, Payslip, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(62)))
+        ));
+
+}
+
+// Ear tag validation
+
+// A national or international ear tag numbering scheme
+trait TagValidator {
+    fn scheme(&self) -> &'static str;
+
+    // Returns true when the normalized tag is valid under this scheme
+    fn is_valid(&self, tag: &str) -> bool;
+}
+
+// USDA animal identification number: 840 followed by 12 digits
+struct Usda840Validator;
+
+impl TagValidator for Usda840Validator {
+    fn scheme(&self) -> &'static str {
+        "USDA 840"
+    }
+
+    fn is_valid(&self, tag: &str) -> bool {
+        tag.len() == 15 && tag.starts_with("840") && tag.chars().all(|c| c.is_ascii_digit())
+    }
+}
+
+// ISO 11784/11785 EID: 3-digit country or manufacturer code and 12-digit national id
+struct IsoEidValidator;
+
+impl TagValidator for IsoEidValidator {
+    fn scheme(&self) -> &'static str {
+        "ISO 11784/11785"
+    }
+
+    fn is_valid(&self, tag: &str) -> bool {
+        tag.len() == 15 && tag.chars().all(|c| c.is_ascii_digit()) && !tag.starts_with("000")
+    }
+}
+
+// UK/EU visual tag: ISO country prefix followed by the herd and animal digits
+struct CountryPrefixedValidator;
+
+impl TagValidator for CountryPrefixedValidator {
+    fn scheme(&self) -> &'static str {
+        "UK/EU visual"
+    }
+
+    fn is_valid(&self, tag: &str) -> bool {
+        match (tag.get(..2), tag.get(2..)) {
+            (Some(prefix), Some(digits)) => {
+                VISUAL_TAG_COUNTRIES.contains(&prefix)
+                    && (6..=12).contains(&digits.len())
+                    && digits.chars().all(|c| c.is_ascii_digit())
+            }
+            _ => false,
+        }
+    }
+}
+
+// Checked in order; the first matching scheme wins
+const TAG_VALIDATORS: [&dyn TagValidator; 3] = [
+    &Usda840Validator,
+    &IsoEidValidator,
+    &CountryPrefixedValidator,
+];
+
+// Strips separators and upper-cases the tag, then validates it against the known schemes
+fn validate_tag(tag_number: &str) -> Result<TagValidation, Message> {
+    let normalized: String = tag_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+
+    TAG_VALIDATORS
+        .iter()
+        .find(|validator| validator.is_valid(&normalized))
+        .map(|validator| TagValidation {
+            tag_number: normalized.clone(),
+            scheme: validator.scheme().to_string(),
+        })
+        .ok_or_else(|| {
+            Message::invalid(
+                "tag_number",
+                FieldErrorCode::InvalidFormat,
+                format!(
+                    "Tag number {} does not match any supported tag scheme",
+                    tag_number
+                ),
+            )
+        })
+}
+
+fn ensure_tag_available(tag_number: &str) -> Result<(), Message> {
+    let existing = TAG_INDEX
+        .with(|index| index.borrow().get(&TagNumber(tag_number.to_string())))
+        .and_then(|animal_id| ANIMALS.with(|animals| animals.borrow().get(&animal_id)));
+
+    match existing {
+        Some(animal) if animal.status == AnimalStatus::Processed => Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!(
+                "Tag number {} belongs to animal {} which has already been slaughtered",
+                tag_number, animal.id
+            ),
+        )
+        .on("animal", animal.id)),
+        Some(animal) => Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!(
+                "Tag number {} is already registered to animal {} at slaughterhouse {}",
+                tag_number, animal.id, animal.slaughterhouse_id
+            ),
+        )
+        .on("animal", animal.id)),
+        None => Ok(()),
+    }
+}
+
+// Carcass grading schemes
+
+// A carcass classification scheme used for grading and payment
+trait GradingScheme {
+    fn name(&self) -> &'static str;
+
+    // Takes a canonical species code
+    fn accepts_species(&self, species: &str) -> bool;
+
+    // Validates the scheme's inputs and returns the combined grade label
+    fn grade(&self, payload: &CarcassGradePayload) -> Result<String, Message>;
+}
+
+// EU SEUROP conformation (S, E, U, R, O, P) and fat class (1-5) for cattle and sheep
+struct SeuropScheme;
+
+impl GradingScheme for SeuropScheme {
+    fn name(&self) -> &'static str {
+        "EU SEUROP"
+    }
+
+    fn accepts_species(&self, species: &str) -> bool {
+        species == "cattle" || species == "sheep"
+    }
+
+    fn grade(&self, payload: &CarcassGradePayload) -> Result<String, Message> {
+        Validator::new()
+            .check(
+                ["S", "E", "U", "R", "O", "P"].contains(&payload.conformation.as_str()),
+                "conformation",
+                FieldErrorCode::InvalidValue,
+                format!("Invalid SEUROP conformation class {}", payload.conformation),
+            )
+            .check(
+                ["1", "2", "3", "4", "5"].contains(&payload.fat_class.as_str()),
+                "fat_class",
+                FieldErrorCode::InvalidValue,
+                format!("Invalid SEUROP fat class {}", payload.fat_class),
+            )
+            .finish()?;
+        Ok(format!("{}{}", payload.conformation, payload.fat_class))
+    }
+}
+
+// USDA beef quality grade and yield grade (1-5)
+struct UsdaBeefScheme;
+
+impl GradingScheme for UsdaBeefScheme {
+    fn name(&self) -> &'static str {
+        "USDA"
+    }
+
+    fn accepts_species(&self, species: &str) -> bool {
+        species == "cattle"
+    }
+
+    fn grade(&self, payload: &CarcassGradePayload) -> Result<String, Message> {
+        let quality_grades = [
+            "Prime",
+            "Choice",
+            "Select",
+            "Standard",
+            "Commercial",
+            "Utility",
+            "Cutter",
+            "Canner",
+        ];
+        Validator::new()
+            .check(
+                quality_grades.contains(&payload.quality_grade.as_str()),
+                "quality_grade",
+                FieldErrorCode::InvalidValue,
+                format!("Invalid USDA quality grade {}", payload.quality_grade),
+            )
+            .check(
+                ["1", "2", "3", "4", "5"].contains(&payload.yield_grade.as_str()),
+                "yield_grade",
+                FieldErrorCode::InvalidValue,
+                format!("Invalid USDA yield grade {}", payload.yield_grade),
+            )
+            .finish()?;
+        Ok(format!(
+            "{} YG{}",
+            payload.quality_grade, payload.yield_grade
+        ))
+    }
+}
+
+// EU pig carcass classification banded on lean meat percentage
+struct PorkLeanMeatScheme;
+
+impl GradingScheme for PorkLeanMeatScheme {
+    fn name(&self) -> &'static str {
+        "EU pig lean meat"
+    }
+
+    fn accepts_species(&self, species: &str) -> bool {
+        species == "pig"
+    }
+
+    fn grade(&self, payload: &CarcassGradePayload) -> Result<String, Message> {
+        let lean = payload.lean_meat_percentage;
+        if !(20.0..=80.0).contains(&lean) {
+            return Err(Message::invalid(
+                "lean_meat_percentage",
+                FieldErrorCode::OutOfRange,
+                format!("Invalid lean meat percentage {}", lean),
+            ));
+        }
+        let class = match lean {
+            l if l >= 60.0 => "S",
+            l if l >= 55.0 => "E",
+            l if l >= 50.0 => "U",
+            l if l >= 45.0 => "R",
+            l if l >= 40.0 => "O",
+            _ => "P",
+        };
+        Ok(class.to_string())
+    }
+}
+
+const GRADING_SCHEMES: [&dyn GradingScheme; 3] =
+    [&SeuropScheme, &UsdaBeefScheme, &PorkLeanMeatScheme];
+
+// Species catalog
+
+// Resolves a species code or alias, ignoring case
+fn find_species(name: &str) -> Option<Species> {
+    let name = name.trim().to_lowercase();
+    SPECIES_CATALOG.with(|catalog| {
+        catalog
+            .borrow()
+            .iter()
+            .map(|(_, species)| species)
+            .find(|species| species.code == name || species.aliases.contains(&name))
+    })
+}
+
+// Canonical code for a stored species name; records predating the catalog fall back to lowercase
+fn species_code(name: &str) -> String {
+    find_species(name)
+        .map(|species| species.code)
+        .unwrap_or_else(|| name.trim().to_lowercase())
+}
+
+fn unknown_species(name: &str) -> Message {
+    Message::not_found_by("species", format!("Unknown species {}", name))
+}
+
+// Resolves the species and checks the live weight against its range
+
+fn validate_animal_species(name: &str, weight: f64) -> Result<Species, Message> {
+    let species = find_species(name).ok_or_else(|| unknown_species(name))?;
+
+    if !(species.min_weight..=species.max_weight).contains(&weight) {
+        return Err(Message::invalid(
+            "weight",
+            FieldErrorCode::OutOfRange,
+            format!(
+                "Weight {} kg is outside the {} range of {}-{} kg",
+                weight, species.code, species.min_weight, species.max_weight
+            ),
+        ));
+    }
+
+    Ok(species)
+}
+
+fn default_species_catalog() -> Vec<SpeciesPayload> {
+    let species = |code: &str, name: &str, aliases: &[&str]| SpeciesPayload {
+        code: code.to_string(),
+        name: name.to_string(),
+        aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        min_temperature: -2.0,
+        max_temperature: 7.0,
+        ..Default::default()
+    };
+
+    vec![
+        SpeciesPayload {
+            min_weight: 150.0,
+            max_weight: 1200.0,
+            expected_dressing_percentage: 58.0,
+            min_ph: 5.4,
+            max_ph: 5.8,
+            shelf_life_days: 21,
+            ..species(
+                "cattle",
+                "Cattle",
+                &["cow", "bull", "steer", "heifer", "bovine", "beef"],
+            )
+        },
+        SpeciesPayload {
+            min_weight: 15.0,
+            max_weight: 120.0,
+            expected_dressing_percentage: 48.0,
+            min_ph: 5.5,
+            max_ph: 5.9,
+            shelf_life_days: 14,
+            ..species("sheep", "Sheep", &["lamb", "ewe", "ram", "ovine", "mutton"])
+        },
+        SpeciesPayload {
+            min_weight: 10.0,
+            max_weight: 100.0,
+            expected_dressing_percentage: 45.0,
+            min_ph: 5.5,
+            max_ph: 5.9,
+            shelf_life_days: 10,
+            ..species("goat", "Goat", &["kid", "caprine"])
+        },
+        SpeciesPayload {
+            min_weight: 20.0,
+            max_weight: 350.0,
+            expected_dressing_percentage: 75.0,
+            min_ph: 5.4,
+            max_ph: 5.9,
+            shelf_life_days: 10,
+            ..species(
+                "pig",
+                "Pig",
+                &["swine", "hog", "sow", "boar", "porcine", "pork"],
+            )
+        },
+    ]
+}
+
+fn seed_species_catalog() {
+    let empty = SPECIES_CATALOG.with(|catalog| catalog.borrow().is_empty());
+    if empty {
+        for payload in default_species_catalog() {
+            if register_species(payload).is_err() {
+                ic_cdk::trap("Default species catalog is invalid");
+            }
+        }
+    }
+}
+
+// Expense categories
+
+fn find_expense_category(code: &str) -> Option<ExpenseCategory> {
+    let code = code.trim().to_lowercase();
+    EXPENSE_CATEGORIES.with(|categories| {
+        categories
+            .borrow()
+            .iter()
+            .map(|(_, category)| category)
+            .find(|category| category.code == code)
+    })
+}
+
+fn unknown_expense_category(code: &str) -> Message {
+    Message::not_found_by(
+        "expense_category",
+        format!("Unknown expense category {}", code),
+    )
+}
+
+// Codes from the top-level category down to the given one
+fn expense_category_path(code: &str) -> Vec<String> {
+    let mut path = Vec::new();
+    let mut next = find_expense_category(code);
+    while let Some(category) = next {
+        if path.contains(&category.code) || path.len() > MAX_EXPENSE_CATEGORY_DEPTH as usize {
+            break;
+        }
+        next = category
+            .parent_code
+            .as_deref()
+            .and_then(find_expense_category);
+        path.push(category.code);
+    }
+    path.reverse();
+    path
+}
+
+fn default_expense_categories() -> Vec<ExpenseCategoryPayload> {
+    let category = |code: &str, name: &str, parent_code: Option<&str>| ExpenseCategoryPayload {
+        code: code.to_string(),
+        name: name.to_string(),
+        parent_code: parent_code.map(|parent| parent.to_string()),
+    };
+
+    vec![
+        category("operating", "Operating costs", None),
+        category("utilities", "Utilities", Some("operating")),
+        category("supplies", "Supplies and consumables", Some("operating")),
+        category("transport", "Transport", Some("operating")),
+        category("maintenance", "Maintenance", None),
+        category(
+            "maintenance_planned",
+            "Planned maintenance",
+            Some("maintenance"),
+        ),
+        category("maintenance_repairs", "Repairs", Some("maintenance")),
+        category("waste", "Waste management", None),
+        category("waste_category_1", "Category 1 by-products", Some("waste")),
+        category("waste_category_2", "Category 2 by-products", Some("waste")),
+        category("waste_category_3", "Category 3 by-products", Some("waste")),
+        category(LABOR_EXPENSE_CATEGORY, "Labor", None),
+        category("administration", "Administration", None),
+        category(UNCATEGORISED_EXPENSES, "Uncategorised", None),
+    ]
+}
+
+fn seed_expense_categories() {
+    let empty = EXPENSE_CATEGORIES.with(|categories| categories.borrow().is_empty());
+    if empty {
+        for payload in default_expense_categories() {
+            if register_expense_category(payload).is_err() {
+                ic_cdk::trap("Default expense categories are invalid");
+            }
+        }
+    }
+}
+
+// Status migration
+
+// Stored layout version; 1 introduced the status and type enums and the employee
+// supervisor flag
+const SCHEMA_VERSION: u64 = 1;
+
+// Record layouts from before the status enums, read once by the migration
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LegacyAnimal {
+    id: u64,
+    slaughterhouse_id: u64,
+    tag_number: String,
+    species: String, // canonical species code from the catalog, e.g., cattle, sheep, goat, pig
+    weight: f64,     // in kilograms
+    arrival_time: u64,
+    status: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LegacyMeatProduct {
+    id: u64,
+    animal_id: u64,
+    slaughterhouse_id: u64,
+    product_type: String, // e.g., steak, ribs, minced meat
+    weight: f64,          // in kilograms
+    price_per_kg: f64,
+    total_price: f64,
+    status: String,
+    created_at: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LegacyEmployee {
+    id: u64,
+    slaughterhouse_id: u64,
+    name: String,
+    role: String,
+    certification: String,
+    hire_date: u64,
+    contact: String,
+    status: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LegacyMaintenanceRecord {
+    id: u64,
+    slaughterhouse_id: u64,
+    equipment_name: String,
+    maintenance_type: String,
+    cost: f64,
+    date: u64,
+    next_maintenance_date: u64,
+    performed_by: String,
+    status: String,
+    notes: String,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LegacyShipment {
+    id: u64,
+    slaughterhouse_id: u64,
+    product_ids: Vec<u64>,
+    destination: String,
+    shipping_date: u64,
+    expected_delivery: u64,
+    temperature_log: Vec<f64>,
+    status: String,
+    tracking_number: String,
+}
+
+// Converts a stored label, logging values that had to fall back to a default
+fn migrate_label<T: StatusLabel>(
+    entity: &str,
+    record_id: u64,
+    field: &str,
+    label: &str,
+    fallback: T,
+) -> T {
+    T::from_label(label).unwrap_or_else(|| {
+        log_migration_issue(entity, record_id, field, label, fallback.as_str());
+        fallback
+    })
+}
+
+fn log_migration_issue(
+    entity: &str,
+    record_id: u64,
+    field: &str,
+    original_value: &str,
+    assigned_value: &str,
+) {
+    let issue_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+    let issue = StatusMigrationIssue {
+        id: issue_id,
+        entity: entity.to_string(),
+        record_id,
+        field: field.to_string(),
+        original_value: original_value.to_string(),
+        assigned_value: assigned_value.to_string(),
+    };
+    STATUS_MIGRATION_ISSUES.with(|issues| {
+        issues.borrow_mut().insert(issue_id, issue);
+    });
+}
+
+// Reads a table in its pre-enum layout; must run before the table itself is touched
+fn read_legacy_table<L: BoundedStorable + Clone>(memory_id: u8) -> Vec<L> {
+    let table: StableBTreeMap<u64, L, Memory> =
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(memory_id))));
+    table.iter().map(|(_, record)| record).collect()
+}
+
+// Rewrites the tables that stored statuses and types as strings
+fn migrate_status_enums() {
+    let animals: Vec<Animal> = read_legacy_table::<LegacyAnimal>(11)
+        .into_iter()
+        .map(|animal| Animal {
+            status: migrate_label(
+                "animal",
+                animal.id,
+                "status",
+                &animal.status,
+                AnimalStatus::Received,
+            ),
+            id: animal.id,
+            slaughterhouse_id: animal.slaughterhouse_id,
+            tag_number: animal.tag_number,
+            species: animal.species,
+            weight: animal.weight,
+            arrival_time: animal.arrival_time,
+        })
+        .collect();
+    // Unknown product statuses fall back to disposed so nothing unexpected is sold
+    let products: Vec<MeatProduct> = read_legacy_table::<LegacyMeatProduct>(12)
+        .into_iter()
+        .map(|product| MeatProduct {
+            status: migrate_label(
+                "meat_product",
+                product.id,
+                "status",
+                &product.status,
+                ProductStatus::Disposed,
+            ),
+            id: product.id,
+            animal_id: product.animal_id,
+            slaughterhouse_id: product.slaughterhouse_id,
+            product_type: product.product_type,
+            weight: product.weight,
+            price_per_kg: product.price_per_kg,
+            total_price: product.total_price,
+            created_at: product.created_at,
+        })
+        .collect();
+    let employees: Vec<Employee> = read_legacy_table::<LegacyEmployee>(15)
+        .into_iter()
+        .map(|employee| Employee {
+            status: migrate_label(
+                "employee",
+                employee.id,
+                "status",
+                &employee.status,
+                EmployeeStatus::Inactive,
+            ),
+            // Supervisors were recognised by their free-text role before the flag existed
+            supervisor: employee.role.trim().eq_ignore_ascii_case("supervisor"),
+            id: employee.id,
+            slaughterhouse_id: employee.slaughterhouse_id,
+            name: employee.name,
+            role: employee.role,
+            certification: employee.certification,
+            hire_date: employee.hire_date,
+            contact: employee.contact,
+        })
+        .collect();
+    let maintenance_records: Vec<MaintenanceRecord> =
+        read_legacy_table::<LegacyMaintenanceRecord>(16)
+            .into_iter()
+            .map(|record| MaintenanceRecord {
+                maintenance_type: migrate_label(
+                    "maintenance",
+                    record.id,
+                    "maintenance_type",
+                    &record.maintenance_type,
+                    MaintenanceType::Corrective,
+                ),
+                status: migrate_label(
+                    "maintenance",
+                    record.id,
+                    "status",
+                    &record.status,
+                    MaintenanceStatus::Scheduled,
+                ),
+                id: record.id,
+                slaughterhouse_id: record.slaughterhouse_id,
+                equipment_name: record.equipment_name,
+                cost: record.cost,
+                date: record.date,
+                next_maintenance_date: record.next_maintenance_date,
+                performed_by: record.performed_by,
+                notes: record.notes,
+            })
+            .collect();
+    let shipments: Vec<Shipment> = read_legacy_table::<LegacyShipment>(18)
+        .into_iter()
+        .map(|shipment| Shipment {
+            status: migrate_label(
+                "shipment",
+                shipment.id,
+                "status",
+                &shipment.status,
+                ShipmentStatus::Preparing,
+            ),
+            id: shipment.id,
+            slaughterhouse_id: shipment.slaughterhouse_id,
+            product_ids: shipment.product_ids,
+            destination: shipment.destination,
+            shipping_date: shipment.shipping_date,
+            expected_delivery: shipment.expected_delivery,
+            temperature_log: shipment.temperature_log,
+            tracking_number: shipment.tracking_number,
+        })
+        .collect();
+
+    ANIMALS.with(|table| {
+        for animal in animals {
+            table.borrow_mut().insert(animal.id, animal);
+        }
+    });
+    MEAT_PRODUCTS.with(|table| {
+        for product in products {
+            table.borrow_mut().insert(product.id, product);
+        }
+    });
+    EMPLOYEES.with(|table| {
+        for employee in employees {
+            table.borrow_mut().insert(employee.id, employee);
+        }
+    });
+    MAINTENANCE_RECORDS.with(|table| {
+        for record in maintenance_records {
+            table.borrow_mut().insert(record.id, record);
+        }
+    });
+    SHIPMENTS.with(|table| {
+        for shipment in shipments {
+            table.borrow_mut().insert(shipment.id, shipment);
+        }
+    });
+}
+
+#[ic_cdk::query]
+fn get_status_migration_issues() -> Vec<StatusMigrationIssue> {
+    STATUS_MIGRATION_ISSUES.with(|issues| issues.borrow().iter().map(|(_, issue)| issue).collect())
+}
+
+#[ic_cdk::init]
+fn init() {
+    STORED_SCHEMA_VERSION.with(|version| {
+        version
+            .borrow_mut()
+            .set(SCHEMA_VERSION)
+            .expect("Schema version update failed");
+    });
+    seed_species_catalog();
+    seed_expense_categories();
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    let stored_version = STORED_SCHEMA_VERSION.with(|version| *version.borrow().get());
+    if stored_version < 1 {
+        migrate_status_enums();
+    }
+    STORED_SCHEMA_VERSION.with(|version| {
+        version
+            .borrow_mut()
+            .set(SCHEMA_VERSION)
+            .expect("Schema version update failed");
+    });
+
+    seed_species_catalog();
+    seed_expense_categories();
+
+    // Index animals registered before the tag index existed. Legacy free-text tags that
+    // match no scheme are left out: they may not fit the index key, and no newly
+    // registered tag can collide with them.
+    let animals: Vec<Animal> =
+        ANIMALS.with(|animals| animals.borrow().iter().map(|(_, animal)| animal).collect());
+    TAG_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for animal in animals {
+            if let Ok(validation) = validate_tag(&animal.tag_number) {
+                let key = TagNumber(validation.tag_number);
+                if !index.contains_key(&key) {
+                    index.insert(key, animal.id);
+                }
+            }
+        }
+    });
+}
+
+// Functions
+
+// Create Slaughterhouse
+#[ic_cdk::update]
+fn create_slaughterhouse(payload: CreateSlaughterhousePayload) -> Result<Slaughterhouse, Message> {
+    Validator::new()
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .max_length("location", &payload.location, MAX_NAME_LENGTH)
+        .text("contact", &payload.contact, MAX_NAME_LENGTH)
+        .text("email", &payload.email, MAX_NAME_LENGTH)
+        .check(
+            payload.capacity > 0,
+            "capacity",
+            FieldErrorCode::OutOfRange,
+            "capacity must be positive",
+        )
+        .finish()?;
+
+    let slaughterhouse_id = ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Counter increment failed");
+
+    let slaughterhouse = Slaughterhouse {
+        id: slaughterhouse_id,
+        name: payload.name,
+        location: payload.location,
+        contact: payload.contact,
+        email: payload.email,
+        capacity: payload.capacity,
+        created_at: time(),
+    };
+
+    SLAUGHTERHOUSES.with(|houses| {
+        houses
+            .borrow_mut()
+            .insert(slaughterhouse_id, slaughterhouse.clone());
+    });
+
+    Ok(slaughterhouse)
+}
+
+// Register Animal
+#[ic_cdk::update]
+fn register_animal(payload: RegisterAnimalPayload) -> Result<Animal, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("tag_number", &payload.tag_number, MAX_NAME_LENGTH)
+        .text("species", &payload.species, MAX_NAME_LENGTH)
+        .positive("weight", payload.weight)
+        .finish()?;
+
+    ensure_not_locked_down(payload.slaughterhouse_id)?;
+
+    let species = validate_animal_species(&payload.species, payload.weight)?;
+    let tag_number = validate_tag(&payload.tag_number)?.tag_number;
+    ensure_tag_available(&tag_number)?;
+    let weighing = check_reading(payload.scale_id, "weight", payload.slaughterhouse_id)?;
+
+    let animal_id = ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Counter increment failed");
+
+    let animal = Animal {
+        id: animal_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        tag_number: tag_number.clone(),
+        species: species.code,
+        weight: payload.weight,
+        arrival_time: time(),
+        status: AnimalStatus::Received,
+    };
+
+    ANIMALS.with(|animals| {
+        animals.borrow_mut().insert(animal_id, animal.clone());
+    });
+    TAG_INDEX.with(|index| {
+        index.borrow_mut().insert(TagNumber(tag_number), animal_id);
+    });
+    record_reading(weighing, "animal", animal_id, animal.weight);
+
+    Ok(animal)
+}
+
+// Create Meat Product
+#[ic_cdk::update]
+fn create_meat_product(payload: CreateMeatProductPayload) -> Result<MeatProduct, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("product_type", &payload.product_type, MAX_NAME_LENGTH)
+        .positive("weight", payload.weight)
+        .positive("price_per_kg", payload.price_per_kg)
+        .finish()?;
+
+    let animal = ANIMALS
+        .with(|animals| animals.borrow().get(&payload.animal_id))
+        .ok_or_else(|| Message::not_found("animal", payload.animal_id))?;
+    if animal.slaughterhouse_id != payload.slaughterhouse_id {
+        return Err(Message::invalid(
+            "animal_id",
+            FieldErrorCode::InvalidValue,
+            "The animal belongs to another slaughterhouse",
+        ));
+    }
+
+    if let Some(species) = find_species(&animal.species) {
+        if !species.allowed_product_types.is_empty()
+            && !species
+                .allowed_product_types
+                .contains(&payload.product_type.to_lowercase())
+        {
+            return Err(Message::invalid(
+                "product_type",
+                FieldErrorCode::InvalidValue,
+                format!(
+                    "Product type {} is not allowed for {}",
+                    payload.product_type, species.code
+                ),
+            ));
+        }
+    }
+
+    ensure_withdrawal_complete(payload.animal_id, time())?;
+
+    ensure_not_locked_down(payload.slaughterhouse_id)?;
+    let weighing = check_reading(payload.scale_id, "weight", payload.slaughterhouse_id)?;
+
+    let meat_product_id = ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Counter increment failed");
+
+    let total_price = payload.weight * payload.price_per_kg;
+
+    let meat_product = MeatProduct {
+        id: meat_product_id,
+        animal_id: payload.animal_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        product_type: payload.product_type,
+        weight: payload.weight,
+        price_per_kg: payload.price_per_kg,
+        total_price,
+        status: ProductStatus::InStock,
+        created_at: time(),
+    };
+
+    MEAT_PRODUCTS.with(|products| {
+        products
+            .borrow_mut()
+            .insert(meat_product_id, meat_product.clone());
+    });
+    record_reading(
+        weighing,
+        "meat_product",
+        meat_product_id,
+        meat_product.weight,
+    );
+
+    Ok(meat_product)
+}
+
+// Record Expense
+#[ic_cdk::update]
+fn record_expense(payload: RecordExpensePayload) -> Result<Expense, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .positive("amount", payload.amount)
+        .max_length("description", &payload.description, MAX_NOTES_LENGTH)
+        .finish()?;
+    let category = find_expense_category(&payload.category).ok_or_else(|| {
+        Message::invalid(
+            "category",
+            FieldErrorCode::UnknownReference,
+            format!("Unknown expense category {}", payload.category),
+        )
+    })?;
+
+    let expense_id = ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Counter increment failed");
+
+    let expense = Expense {
+        id: expense_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        date: time(),
+        category: category.code,
+        amount: payload.amount,
+        description: payload.description,
+    };
+
+    EXPENSES.with(|expenses| {
+        expenses.borrow_mut().insert(expense_id, expense.clone());
+    });
+
+    Ok(expense)
+}
+
+// Register Expense Category
+#[ic_cdk::update]
+fn register_expense_category(payload: ExpenseCategoryPayload) -> Result<ExpenseCategory, Message> {
+    let code = payload.code.trim().to_lowercase();
+    if let Some(existing) = find_expense_category(&code) {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!("Expense category {} already exists", code),
+        )
+        .on("expense_category", existing.id));
+    }
+
+    store_expense_category(None, payload)
+}
+
+// Update Expense Category, renaming it or moving it under another parent
+#[ic_cdk::update]
+fn update_expense_category(payload: ExpenseCategoryPayload) -> Result<ExpenseCategory, Message> {
+    let existing = find_expense_category(&payload.code)
+        .ok_or_else(|| unknown_expense_category(&payload.code))?;
+
+    store_expense_category(Some(existing.id), payload)
+}
+
+// Validates and stores a category; new categories are given an id once they are valid
+fn store_expense_category(
+    category_id: Option<u64>,
+    payload: ExpenseCategoryPayload,
+) -> Result<ExpenseCategory, Message> {
+    let code = payload.code.trim().to_lowercase();
+    let parent_code = payload
+        .parent_code
+        .as_deref()
+        .map(|parent| parent.trim().to_lowercase());
+
+    let mut validator = Validator::new();
+    validator
+        .text("code", &code, MAX_NAME_LENGTH)
+        .check(
+            code.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+            "code",
+            FieldErrorCode::InvalidFormat,
+            "Codes may only contain letters, digits and underscores",
+        )
+        .text("name", &payload.name, MAX_NAME_LENGTH);
+    if let Some(parent_code) = &parent_code {
+        let parent_path = expense_category_path(parent_code);
+        validator
+            .check(
+                !parent_path.is_empty(),
+                "parent_code",
+                FieldErrorCode::UnknownReference,
+                format!("Unknown expense category {}", parent_code),
+            )
+            .check(
+                !parent_path.contains(&code),
+                "parent_code",
+                FieldErrorCode::InvalidValue,
+                "A category cannot be moved under itself or its subcategories",
+            )
+            .check(
+                parent_path.len() + expense_subtree_height(&code)
+                    <= MAX_EXPENSE_CATEGORY_DEPTH as usize,
+                "parent_code",
+                FieldErrorCode::OutOfRange,
+                format!(
+                    "Expense categories are limited to {} levels",
+                    MAX_EXPENSE_CATEGORY_DEPTH + 1
+                ),
+            );
+    }
+    validator.finish()?;
+
+    let category_id = category_id.unwrap_or_else(|| {
+        ID_COUNTER.with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter
+                .borrow_mut()
+                .set(current_value + 1)
+                .expect("Counter increment failed");
+            current_value
+        })
+    });
+    let category = ExpenseCategory {
+        id: category_id,
+
+        code,
+        name: payload.name,
+        parent_code,
+        updated_at: time(),
+    };
+
+    EXPENSE_CATEGORIES.with(|categories| {
+        categories
+            .borrow_mut()
+            .insert(category_id, category.clone());
+    });
+
+    Ok(category)
+}
+
+// Levels below a category, 0 for a category without subcategories
+fn expense_subtree_height(code: &str) -> usize {
+    fn height(code: &str, categories: &[ExpenseCategory], depth: u32) -> usize {
+        if depth > MAX_EXPENSE_CATEGORY_DEPTH {
+            return 0;
+        }
+        categories
+            .iter()
+            .filter(|category| category.parent_code.as_deref() == Some(code))
+            .map(|category| 1 + height(&category.code, categories, depth + 1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    let categories: Vec<ExpenseCategory> = EXPENSE_CATEGORIES.with(|categories| {
+        categories
+            .borrow()
+            .iter()
+            .map(|(_, category)| category)
+            .collect()
+    });
+    height(code, &categories, 0)
+}
+
+#[ic_cdk::query]
+fn get_expense_categories() -> Vec<ExpenseCategory> {
+    EXPENSE_CATEGORIES.with(|categories| {
+        categories
+            .borrow()
+            .iter()
+            .map(|(_, category)| category)
+            .collect()
+    })
+}
+
+// Expenses, maintenance costs and waste costs of a slaughterhouse by expense category code
+fn categorised_costs(slaughterhouse_id: u64) -> Vec<(String, f64)> {
+    let mut costs: Vec<(String, f64)> = EXPENSES.with(|expenses| {
+        expenses
+            .borrow()
+            .iter()
+            .filter(|(_, expense)| expense.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, expense)| (expense.category, expense.amount))
+            .collect()
+    });
+
+    MAINTENANCE_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| record.slaughterhouse_id == slaughterhouse_id)
+            .for_each(|(_, record)| {
+                let code = MAINTENANCE_EXPENSE_CATEGORIES
+                    .iter()
+                    .find(|(maintenance_type, _)| *maintenance_type == record.maintenance_type)
+                    .map(|(_, code)| *code)
+                    .unwrap_or("maintenance");
+                costs.push((code.to_string(), record.cost));
+            });
+    });
+
+    WASTE_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| record.slaughterhouse_id == slaughterhouse_id)
+            .for_each(|(_, record)| {
+                let code = WASTE_EXPENSE_CATEGORIES
+                    .iter()
+                    .find(|(category, _)| Some(*category) == record.category)
+                    .map(|(_, code)| *code)
+                    .unwrap_or("waste");
+                costs.push((code.to_string(), record.cost));
+            });
+    });
+
+    PAYSLIPS.with(|payslips| {
+        payslips
+            .borrow()
+            .iter()
+            .filter(|(_, payslip)| payslip.slaughterhouse_id == slaughterhouse_id)
+            .for_each(|(_, payslip)| {
+                costs.push((LABOR_EXPENSE_CATEGORY.to_string(), payslip.gross_pay));
+            });
+    });
+
+    costs
+}
+
+// Rolls costs up to their category at the given depth; costs booked on a shallower
+// category stay there, so the totals always add up to the overall cost
+fn expense_totals(slaughterhouse_id: u64, depth: u32) -> Vec<ExpenseCategoryTotal> {
+    let mut totals: BTreeMap<String, f64> = BTreeMap::new();
+    let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+    for (code, amount) in categorised_costs(slaughterhouse_id) {
+        let path = paths
+            .entry(code.clone())
+            .or_insert_with(|| expense_category_path(&code));
+        let rolled_up = path
+            .get(depth as usize)
+            .or_else(|| path.last())
+            .cloned()
+            .unwrap_or_else(|| UNCATEGORISED_EXPENSES.to_string());
+        *totals.entry(rolled_up).or_insert(0.0) += amount;
+    }
+
+    totals
+        .into_iter()
+        .map(|(code, total)| {
+            let category = find_expense_category(&code);
+            ExpenseCategoryTotal {
+                name: category
+                    .as_ref()
+                    .map(|category| category.name.clone())
+                    .unwrap_or_else(|| code.clone()),
+                parent_code: category.and_then(|category| category.parent_code),
+                depth: expense_category_path(&code).len().saturating_sub(1) as u32,
+                code,
+                total,
+            }
+        })
+        .collect()
+}
+
+// Expense breakdown at a level of the category tree, 0 being the top-level categories
+#[ic_cdk::query]
+fn get_expenses_by_category(
+    slaughterhouse_id: u64,
+    depth: u32,
+) -> Result<Vec<ExpenseCategoryTotal>, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
+
+    Ok(expense_totals(slaughterhouse_id, depth))
+}
+
+// Calculate Total Revenue
+#[ic_cdk::query]
+fn calculate_total_revenue(slaughterhouse_id: u64) -> Result<f64, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
+
+    let total_revenue: f64 = MEAT_PRODUCTS.with(|products| {
+        products
+            .borrow()
+            .iter()
+            .filter(|(_, product)| product.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, product)| product.total_price)
+            .sum()
+    });
+
+    Ok(total_revenue)
+}
+
+// Calculate Total Expenses
+#[ic_cdk::query]
+fn calculate_total_expenses(slaughterhouse_id: u64) -> Result<f64, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
+
+    let total_expenses: f64 = EXPENSES.with(|expenses| {
+        expenses
+            .borrow()
+            .iter()
+            .filter(|(_, expense)| expense.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, expense)| expense.amount)
+            .sum()
+    });
+
+    Ok(total_expenses)
+}
+
+#[ic_cdk::update]
+fn perform_quality_inspection(
+    payload: QualityInspectionPayload,
+) -> Result<QualityInspection, Message> {
+    Validator::new()
+        .range(
+            "temperature",
+            payload.temperature,
+            TEMPERATURE_RANGE.0,
+            TEMPERATURE_RANGE.1,
+        )
+        .range("ph_level", payload.ph_level, PH_RANGE.0, PH_RANGE.1)
+        .max_length("product_type", &payload.product_type, MAX_NAME_LENGTH)
+        .max_length(
+            "visual_inspection",
+            &payload.visual_inspection,
+            MAX_NOTES_LENGTH,
+        )
+        .max_length("notes", &payload.notes, MAX_NOTES_LENGTH)
+        .max_count("answers", payload.answers.len(), MAX_TEMPLATE_QUESTIONS)
+        .finish()?;
+
+    // Validate animal exists
+    let animal = ANIMALS
+        .with(|animals| animals.borrow().get(&payload.animal_id))
+        .ok_or_else(|| Message::not_found("animal", payload.animal_id))?;
+
+    let inspector = caller_employee()?;
+    if inspector.status != EmployeeStatus::Active
+        || inspector.slaughterhouse_id != animal.slaughterhouse_id
+    {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Inspector must be an active employee of the animal's slaughterhouse",
+        )
+        .on("employee", inspector.id));
+    }
+    if !has_valid_certification(inspector.id, INSPECTOR_CERTIFICATION, time()) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Inspector does not hold a valid inspector certification",
+        )
+        .on("employee", inspector.id));
+    }
+
+    let temperature_reading = check_reading(
+        Some(payload.thermometer_id),
+        "temperature",
+        animal.slaughterhouse_id,
+    )?;
+    let ph_reading = check_reading(Some(payload.ph_meter_id), "ph", animal.slaughterhouse_id)?;
+
+    let (criteria, criteria_source) =
+        acceptance_criteria_for(&animal.species, &payload.product_type)?;
+
+    let template = match payload.template_id {
+        Some(template_id) => Some(current_template(template_id)?),
+        None if payload.answers.is_empty() => None,
+        None => {
+            return Err(Message::invalid(
+                "template_id",
+                FieldErrorCode::Required,
+                "Answers require an inspection template",
+            ))
+        }
+    };
+    let answers = match &template {
+        Some(template) => evaluate_answers(template, &payload.answers)?,
+        None => Vec::new(),
+    };
+
+    // The verdict is computed from the measurements and answers, never supplied by the caller
+    let mut failure_reasons = Vec::new();
+    if !(criteria.min_temperature..=criteria.max_temperature).contains(&payload.temperature) {
+        failure_reasons.push(format!(
+            "Temperature {} outside {}-{}",
+            payload.temperature, criteria.min_temperature, criteria.max_temperature
+        ));
+    }
+    if !(criteria.min_ph..=criteria.max_ph).contains(&payload.ph_level) {
+        failure_reasons.push(format!(
+            "pH {} outside {}-{}",
+            payload.ph_level, criteria.min_ph, criteria.max_ph
+        ));
+    }
+    for answer in answers.iter().filter(|answer| !answer.acceptable) {
+        failure_reasons.push(format!("Question {} failed", answer.question_code));
+    }
+
+    let inspection_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let inspection = QualityInspection {
+        id: inspection_id,
+        animal_id: payload.animal_id,
+        inspector_name: inspector.name,
+        inspector_id: Some(inspector.id),
+        inspection_date: time(),
+        temperature: payload.temperature,
+        ph_level: payload.ph_level,
+        visual_inspection: payload.visual_inspection,
+        passed: failure_reasons.is_empty(),
+        notes: payload.notes,
+    };
+
+    let verdict = InspectionVerdict {
+        inspection_id,
+        criteria_source,
+        automatic_passed: inspection.passed,
+        failure_reasons,
+        ..Default::default()
+    };
+
+    QUALITY_INSPECTIONS.with(|inspections| {
+        inspections
+            .borrow_mut()
+            .insert(inspection_id, inspection.clone());
+    });
+    INSPECTION_VERDICTS.with(|verdicts| {
+        verdicts.borrow_mut().insert(inspection_id, verdict);
+    });
+    record_reading(
+        temperature_reading,
+        "quality_inspection",
+        inspection_id,
+        inspection.temperature,
+    );
+    record_reading(
+        ph_reading,
+        "quality_inspection",
+        inspection_id,
+        inspection.ph_level,
+    );
+    if let Some(template) = template {
+        let response = InspectionResponse {
+            inspection_id,
+            template_id: template.id,
+            inspection_type: template.inspection_type,
+            template_version: template.version,
+            answers,
+        };
+        INSPECTION_RESPONSES.with(|responses| {
+            responses.borrow_mut().insert(inspection_id, response);
+        });
+    }
+
+    Ok(inspection)
+}
+
+// Create Inspection Template, superseding earlier versions for the inspection type
+#[ic_cdk::update]
+fn create_inspection_template(
+    payload: InspectionTemplatePayload,
+) -> Result<InspectionTemplate, Message> {
+    let inspection_type = payload.inspection_type.trim().to_lowercase();
+    let mut validator = Validator::new();
+    validator
+        .text("inspection_type", &inspection_type, MAX_NAME_LENGTH)
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .check(
+            !payload.questions.is_empty(),
+            "questions",
+            FieldErrorCode::Required,
+            "At least one question is required",
+        )
+        .check(
+            payload.questions.len() <= MAX_TEMPLATE_QUESTIONS,
+            "questions",
+            FieldErrorCode::TooMany,
+            format!("At most {} questions are allowed", MAX_TEMPLATE_QUESTIONS),
+        );
+
+    let mut codes = Vec::new();
+    for (index, question) in payload.questions.iter().enumerate() {
+        let field = |name: &str| format!("questions[{}].{}", index, name);
+        validator
+            .text(&field("code"), &question.code, MAX_QUESTION_CODE_LENGTH)
+            .check(
+                !codes.contains(&question.code),
+                &field("code"),
+                FieldErrorCode::Duplicate,
+                format!("Duplicate question code {}", question.code),
+            )
+            .text(&field("text"), &question.text, MAX_QUESTION_TEXT_LENGTH);
+        codes.push(question.code.clone());
+
+        let ranged = matches!(
+            question.answer_type,
+            AnswerType::Score | AnswerType::Numeric
+        );
+        validator
+            .check(
+                !ranged
+                    || (question.min_value.is_finite()
+                        && question.max_value.is_finite()
+                        && question.min_value < question.max_value),
+                &field("max_value"),
+                FieldErrorCode::OutOfRange,
+                format!("Question {} needs a valid range", question.code),
+            )
+            .check(
+                question.answer_type != AnswerType::Score
+                    || (question.min_value.fract() == 0.0 && question.max_value.fract() == 0.0),
+                &field("min_value"),
+                FieldErrorCode::InvalidValue,
+                format!(
+                    "Score scale of question {} must use whole numbers",
+                    question.code
+                ),
+            );
+    }
+    validator.finish()?;
+
+    let version = INSPECTION_TEMPLATES.with(|templates| {
+        templates
+            .borrow()
+            .iter()
+            .filter(|(_, template)| template.inspection_type == inspection_type)
+            .map(|(_, template)| template.version)
+            .max()
+            .unwrap_or(0)
+            + 1
+    });
+
+    let template_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let template = InspectionTemplate {
+        id: template_id,
+        inspection_type,
+        version,
+        name: payload.name,
+        questions: payload.questions,
+        created_at: time(),
+    };
+
+    INSPECTION_TEMPLATES.with(|templates| {
+        templates.borrow_mut().insert(template_id, template.clone());
+    });
+
+    Ok(template)
+}
+
+// Latest version of a template for its inspection type
+fn latest_template(inspection_type: &str) -> Option<InspectionTemplate> {
+    INSPECTION_TEMPLATES.with(|templates| {
+        templates
+            .borrow()
+            .iter()
+            .map(|(_, template)| template)
+            .filter(|template| template.inspection_type == inspection_type)
+            .max_by_key(|template| template.version)
+    })
+}
+
+// Inspections are recorded against the latest version only, so answers of one
+// version are never mixed with questions of another
+fn current_template(template_id: u64) -> Result<InspectionTemplate, Message> {
+    let template = INSPECTION_TEMPLATES
+        .with(|templates| templates.borrow().get(&template_id))
+        .ok_or_else(|| Message::not_found("inspection_template", template_id))?;
+
+    match latest_template(&template.inspection_type) {
+        Some(latest) if latest.id != template.id => Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Template version {} is superseded by version {} (template {})",
+                template.version, latest.version, latest.id
+            ),
+        )
+        .on("inspection_template", template.id)),
+        _ => Ok(template),
+    }
+}
+
+// Checks each answer against its question and marks whether it is acceptable
+fn evaluate_answers(
+    template: &InspectionTemplate,
+    answers: &[InspectionAnswerPayload],
+) -> Result<Vec<InspectionAnswer>, Message> {
+    let mut validator = Validator::new();
+    for (index, answer) in answers.iter().enumerate() {
+        let field = format!("answers[{}].question_code", index);
+        validator
+            .check(
+                !answers[..index]
+                    .iter()
+                    .any(|earlier| earlier.question_code == answer.question_code),
+                &field,
+                FieldErrorCode::Duplicate,
+                format!("Question {} answered more than once", answer.question_code),
+            )
+            .check(
+                template
+                    .questions
+                    .iter()
+                    .any(|question| question.code == answer.question_code),
+                &field,
+                FieldErrorCode::InvalidValue,
+                format!(
+                    "Question {} is not part of template version {}",
+                    answer.question_code, template.version
+                ),
+            );
+    }
+
+    let mut evaluated = Vec::new();
+    for question in &template.questions {
+        let (index, answer) = match answers
+            .iter()
+            .enumerate()
+            .find(|(_, answer)| answer.question_code == question.code)
+        {
+            Some(found) => found,
+            None => {
+                validator.check(
+                    !question.required,
+                    "answers",
+                    FieldErrorCode::Required,
+                    format!("Question {} requires an answer", question.code),
+                );
+                continue;
+            }
+        };
+
+        let mut result = InspectionAnswer {
+            question_code: question.code.clone(),
+            answer_type: question.answer_type,
+            acceptable: true,
+            ..Default::default()
+        };
+        let valid = match question.answer_type {
+            AnswerType::YesNo => match answer.yes_no {
+                Some(yes_no) => {
+                    result.yes_no = yes_no;
+                    result.acceptable = yes_no == question.expected_answer;
+                    true
+                }
+                None => false,
+            },
+            AnswerType::Score => match answer.value.filter(|value| value.is_finite()) {
+                Some(value) => {
+                    result.value = value;
+                    value.fract() == 0.0
+                        && (question.min_value..=question.max_value).contains(&value)
+                }
+                None => false,
+            },
+            AnswerType::Numeric => match answer.value.filter(|value| value.is_finite()) {
+                Some(value) => {
+                    result.value = value;
+                    result.acceptable = (question.min_value..=question.max_value).contains(&value);
+                    true
+                }
+                None => false,
+            },
+            AnswerType::Photo => {
+                result.photo_reference = answer.photo_reference.clone().unwrap_or_default();
+                !result.photo_reference.is_empty()
+                    && result.photo_reference.len() <= MAX_PHOTO_REFERENCE_LENGTH
+            }
+        };
+        validator.check(
+            valid,
+            &format!("answers[{}]", index),
+            FieldErrorCode::InvalidValue,
+            format!(
+                "Invalid {} answer to question {}",
+                question.answer_type.as_str(),
+                question.code
+            ),
+        );
+        evaluated.push(result);
+    }
+
+    validator.finish()?;
+    Ok(evaluated)
+}
+
+#[ic_cdk::query]
+fn get_inspection_template(inspection_type: String) -> Result<InspectionTemplate, Message> {
+    latest_template(&inspection_type.trim().to_lowercase()).ok_or_else(|| {
+        Message::not_found_by(
+            "inspection_template",
+            format!("No inspection template for {}", inspection_type),
+        )
+    })
+}
+
+#[ic_cdk::query]
+fn get_inspection_template_versions(inspection_type: String) -> Vec<InspectionTemplate> {
+    let inspection_type = inspection_type.trim().to_lowercase();
+    let mut versions: Vec<InspectionTemplate> = INSPECTION_TEMPLATES.with(|templates| {
+        templates
+            .borrow()
+            .iter()
+            .map(|(_, template)| template)
+            .filter(|template| template.inspection_type == inspection_type)
+            .collect()
+    });
+    versions.sort_by_key(|template| template.version);
+    versions
+}
+
+#[ic_cdk::query]
+fn get_inspection_response(inspection_id: u64) -> Result<InspectionResponse, Message> {
+    INSPECTION_RESPONSES
+        .with(|responses| responses.borrow().get(&inspection_id))
+        .ok_or_else(|| Message::not_found("inspection_response", inspection_id))
+}
+
+// Criteria for the species and product type, falling back to species-wide criteria
+// and then to the species catalog ranges
+fn acceptance_criteria_for(
+    species: &str,
+    product_type: &str,
+) -> Result<(AcceptanceCriteria, String), Message> {
+    let species_code = species_code(species);
+    let product_type = product_type.trim().to_lowercase();
+
+    let configured = ACCEPTANCE_CRITERIA.with(|criteria| {
+        let criteria = criteria.borrow();
+        let matching = |product_type: &str| {
+            criteria
+                .iter()
+                .map(|(_, entry)| entry)
+                .find(|entry| entry.species == species_code && entry.product_type == product_type)
+        };
+        matching(&product_type).or_else(|| matching(""))
+    });
+    if let Some(criteria) = configured {
+        let source = format!("acceptance criteria {}", criteria.id);
+        return Ok((criteria, source));
+    }
+
+    let species = find_species(&species_code).ok_or_else(|| {
+        Message::not_found_by(
+            "acceptance_criteria",
+            format!("No acceptance criteria for species {}", species_code),
+        )
+    })?;
+    Ok((
+        AcceptanceCriteria {
+            species: species.code,
+            product_type,
+            min_temperature: species.min_temperature,
+            max_temperature: species.max_temperature,
+            min_ph: species.min_ph,
+            max_ph: species.max_ph,
+            ..Default::default()
+        },
+        "species catalog".to_string(),
+    ))
+}
+
+// Supervisors may override inspection verdicts: controllers and active employees
+// flagged as supervisors
+fn is_supervisor(principal: &candid::Principal) -> bool {
+    if is_controller(principal) {
+        return true;
+    }
+    match principal_employee(principal) {
+        Some(employee) => employee.supervisor && employee.status == EmployeeStatus::Active,
+        None => false,
+    }
+}
+
+// Set Acceptance Criteria
+#[ic_cdk::update]
+fn set_acceptance_criteria(
+    payload: AcceptanceCriteriaPayload,
+) -> Result<AcceptanceCriteria, Message> {
+    Validator::new()
+        .text("species", &payload.species, MAX_NAME_LENGTH)
+        .max_length("product_type", &payload.product_type, MAX_NAME_LENGTH)
+        .bounds(
+            "temperature",
+            payload.min_temperature,
+            payload.max_temperature,
+            TEMPERATURE_RANGE,
+        )
+        .bounds("ph", payload.min_ph, payload.max_ph, PH_RANGE)
+        .finish()?;
+
+    let species =
+        find_species(&payload.species).ok_or_else(|| unknown_species(&payload.species))?;
+    let product_type = payload.product_type.trim().to_lowercase();
+
+    let existing_id = ACCEPTANCE_CRITERIA.with(|criteria| {
+        criteria
+            .borrow()
+            .iter()
+            .find(|(_, entry)| entry.species == species.code && entry.product_type == product_type)
+            .map(|(id, _)| id)
+    });
+
+    let criteria_id = existing_id.unwrap_or_else(|| {
+        ID_COUNTER.with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter
+                .borrow_mut()
+                .set(current_value + 1)
+                .expect("Counter increment failed");
+            current_value
+        })
+    });
+
+    let criteria = AcceptanceCriteria {
+        id: criteria_id,
+        species: species.code,
+        product_type,
+        min_temperature: payload.min_temperature,
+        max_temperature: payload.max_temperature,
+        min_ph: payload.min_ph,
+        max_ph: payload.max_ph,
+        updated_at: time(),
+    };
+
+    ACCEPTANCE_CRITERIA.with(|criteria_map| {
+        criteria_map
+            .borrow_mut()
+            .insert(criteria_id, criteria.clone());
+    });
+
+    Ok(criteria)
+}
+
+// Override Inspection Verdict, restricted to supervisors
+#[ic_cdk::update]
+fn override_inspection_verdict(
+    payload: VerdictOverridePayload,
+) -> Result<QualityInspection, Message> {
+    let supervisor = caller();
+    if !is_supervisor(&supervisor) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a supervisor can override an inspection verdict",
+        ));
+    }
+
+    Validator::new()
+        .text("reason", &payload.reason, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let mut inspection = QUALITY_INSPECTIONS
+        .with(|inspections| inspections.borrow().get(&payload.inspection_id))
+        .ok_or_else(|| Message::not_found("quality_inspection", payload.inspection_id))?;
+
+    let mut verdict = INSPECTION_VERDICTS
+        .with(|verdicts| verdicts.borrow().get(&payload.inspection_id))
+        .unwrap_or(InspectionVerdict {
+            inspection_id: inspection.id,
+            criteria_source: "caller supplied".to_string(),
+            automatic_passed: inspection.passed,
+            ..Default::default()
+        });
+
+    verdict.overridden = true;
+    verdict.override_passed = payload.passed;
+    verdict.override_reason = payload.reason;
+    verdict.override_by = supervisor.to_text();
+    verdict.overridden_at = time();
+    inspection.passed = payload.passed;
+
+    QUALITY_INSPECTIONS.with(|inspections| {
+        inspections
+            .borrow_mut()
+            .insert(inspection.id, inspection.clone());
+    });
+    INSPECTION_VERDICTS.with(|verdicts| {
+        verdicts.borrow_mut().insert(inspection.id, verdict);
+    });
+
+    Ok(inspection)
+}
+
+#[ic_cdk::query]
+fn get_inspection_verdict(inspection_id: u64) -> Result<InspectionVerdict, Message> {
+    INSPECTION_VERDICTS
+        .with(|verdicts| verdicts.borrow().get(&inspection_id))
+        .ok_or_else(|| Message::not_found("inspection_verdict", inspection_id))
+}
+
+#[ic_cdk::update]
+fn register_employee(payload: EmployeePayload) -> Result<Employee, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .text("role", &payload.role, MAX_NAME_LENGTH)
+        .max_length("certification", &payload.certification, MAX_NAME_LENGTH)
+        .max_length("contact", &payload.contact, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let employee_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let employee = Employee {
+        id: employee_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        name: payload.name,
+        role: payload.role,
+        certification: payload.certification,
+        hire_date: time(),
+        contact: payload.contact,
+        status: EmployeeStatus::Active,
+        supervisor: false,
+    };
+
+    EMPLOYEES.with(|employees| {
+        employees.borrow_mut().insert(employee_id, employee.clone());
+    });
+
+    Ok(employee)
+}
+
+#[ic_cdk::update]
+fn schedule_maintenance(payload: MaintenancePayload) -> Result<MaintenanceRecord, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("equipment_name", &payload.equipment_name, MAX_NAME_LENGTH)
+        .non_negative("estimated_cost", payload.estimated_cost)
+        .max_length("notes", &payload.notes, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let maintenance_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let record = MaintenanceRecord {
+        id: maintenance_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        equipment_name: payload.equipment_name,
+        maintenance_type: payload.maintenance_type,
+        cost: payload.estimated_cost,
+        date: payload.scheduled_date,
+        next_maintenance_date: payload.scheduled_date + 7_884_000, // Default to 3 months
+        performed_by: "".to_string(),
+        status: MaintenanceStatus::Scheduled,
+        notes: payload.notes,
+    };
+
+    MAINTENANCE_RECORDS.with(|records| {
+        records.borrow_mut().insert(maintenance_id, record.clone());
+    });
+
+    Ok(record)
+}
+
+#[ic_cdk::update]
+fn create_shipment(payload: ShipmentPayload) -> Result<Shipment, Message> {
+    let mut validator = Validator::new();
+    validator
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("destination", &payload.destination, MAX_NAME_LENGTH)
+        .check(
+            !payload.product_ids.is_empty(),
+            "product_ids",
+            FieldErrorCode::Required,
+            "At least one product is required",
+        )
+        .max_count(
+            "product_ids",
+            payload.product_ids.len(),
+            MAX_SHIPMENT_PRODUCTS,
+        );
+    for (index, product_id) in payload.product_ids.iter().enumerate() {
+        validator.check(
+            !payload.product_ids[..index].contains(product_id),
+            &format!("product_ids[{}]", index),
+            FieldErrorCode::Duplicate,
+            format!("Product {} is listed more than once", product_id),
+        );
+    }
+    validator.finish()?;
+
+    // Validate all products exist and none is quarantined or held for lab results
+    for product_id in &payload.product_ids {
+        let product = MEAT_PRODUCTS
+            .with(|products| products.borrow().get(product_id))
+            .ok_or_else(|| Message::not_found("product", *product_id))?;
+        if product.slaughterhouse_id != payload.slaughterhouse_id {
+            return Err(Message::invalid(
+                "product_ids",
+                FieldErrorCode::InvalidValue,
+                format!("Product {} belongs to another slaughterhouse", product_id),
+            ));
+        }
+
+        if let Some(lockdown) = active_lockdown(product.slaughterhouse_id) {
+            if is_product_exposed(&lockdown, &product) {
+                return Err(Message::error(
+                    ErrorCode::Blocked,
+                    format!(
+                        "Product {} is quarantined by lockdown {} ({})",
+                        product_id, lockdown.id, lockdown.disease
+                    ),
+                )
+                .on("product", *product_id));
+            }
+        }
+
+        if let Some((sample_id, reason)) = product_hold(&product) {
+            return Err(Message::error(
+                ErrorCode::Blocked,
+                format!(
+                    "Product {} is on hold: sample {} {}",
+                    product_id, sample_id, reason
+                ),
+            )
+            .on("product", *product_id));
+        }
+    }
+
+    let shipment_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let tracking_number = format!("SH{:0>6}", shipment_id);
+
+    let shipment = Shipment {
+        id: shipment_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        product_ids: payload.product_ids,
+        destination: payload.destination,
+        shipping_date: time(),
+        expected_delivery: payload.expected_delivery,
+        temperature_log: Vec::new(),
+        status: ShipmentStatus::Preparing,
+        tracking_number,
+    };
+
+    SHIPMENTS.with(|shipments| {
+        shipments.borrow_mut().insert(shipment_id, shipment.clone());
+    });
+
+    Ok(shipment)
+}
+
+#[ic_cdk::query]
+fn generate_financial_analytics(slaughterhouse_id: u64) -> Result<FinancialMetrics, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
+
+    let total_revenue = calculate_total_revenue(slaughterhouse_id)?;
+    let total_expenses = calculate_total_expenses(slaughterhouse_id)?;
+
+    let maintenance_costs: f64 = MAINTENANCE_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| record.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, record)| record.cost)
+            .sum()
+    });
+
+    let waste_management_costs: f64 = WASTE_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| record.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, record)| record.cost)
+            .sum()
+    });
+
+    let labor_costs: f64 = PAYSLIPS.with(|payslips| {
+        payslips
+            .borrow()
+            .iter()
+            .filter(|(_, payslip)| payslip.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, payslip)| payslip.gross_pay)
+            .sum()
+    });
+
+    let animals_processed = animals_processed(slaughterhouse_id, 0, u64::MAX);
+    let per_head = |total: f64| -> f64 {
+        if animals_processed > 0 {
+            total / animals_processed as f64
+        } else {
+            0.0
+        }
+    };
+
+    let mut revenue_by_product: HashMap<String, f64> = HashMap::new();
+    MEAT_PRODUCTS.with(|products| {
+        products
+            .borrow()
+            .iter()
+            .filter(|(_, product)| product.slaughterhouse_id == slaughterhouse_id)
+            .for_each(|(_, product)| {
+                *revenue_by_product
+                    .entry(product.product_type.clone())
+                    .or_insert(0.0) += product.total_price;
+            });
+    });
+
+    let metrics = FinancialMetrics {
+        total_revenue,
+        total_expenses,
+        profit_margin: ((total_revenue - total_expenses) / total_revenue * 100.0).max(0.0),
+        operating_costs: total_expenses - maintenance_costs - waste_management_costs,
+        maintenance_costs,
+        labor_costs,
+        waste_management_costs,
+        revenue_by_product,
+        expenses_by_category: expense_totals(slaughterhouse_id, 0)
+            .into_iter()
+            .map(|total| (total.code, total.total))
+            .collect(),
+        animals_processed,
+        labor_cost_per_head: per_head(labor_costs),
+        cost_per_head: per_head(
+            total_expenses + maintenance_costs + waste_management_costs + labor_costs,
+        ),
+    };
+
+    Ok(metrics)
+}
+
+// Manage Waste Disposal, enforcing the disposal routes of the by-product category
+#[ic_cdk::update]
+fn manage_waste_disposal(payload: WasteDisposalPayload) -> Result<WasteRecord, Message> {
+    let disposal_method = payload.disposal_method.trim().to_lowercase();
+    let mut validator = Validator::new();
+    validator
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("waste_type", &payload.waste_type, MAX_NAME_LENGTH)
+        .positive("quantity", payload.quantity)
+        .check(
+            disposal_route_permitted(payload.category, &disposal_method),
+            "disposal_method",
+            FieldErrorCode::InvalidValue,
+            format!(
+                "{} material cannot be disposed of by {}, expected one of {}",
+                payload.category.label(),
+                payload.disposal_method,
+                disposal_routes(payload.category).join(", ")
+            ),
+        )
+        .non_negative("cost", payload.cost)
+        .text("handled_by", &payload.handled_by, MAX_NAME_LENGTH)
+        .check(
+            payload.source_animal_id.is_none() || payload.source_product_id.is_none(),
+            "source_product_id",
+            FieldErrorCode::InvalidValue,
+            "Link either the source animal or the condemned product, not both",
+        );
+    if let Some(animal_id) = payload.source_animal_id {
+        validator.reference("source_animal_id", "animal", animal_id);
+    }
+    if let Some(product_id) = payload.source_product_id {
+        validator.reference("source_product_id", "product", product_id);
+    }
+    validator.finish()?;
+
+    let source_animal = payload
+        .source_animal_id
+        .and_then(|animal_id| ANIMALS.with(|animals| animals.borrow().get(&animal_id)));
+    let source_product = payload
+        .source_product_id
+        .and_then(|product_id| MEAT_PRODUCTS.with(|products| products.borrow().get(&product_id)));
+
+    let source_slaughterhouse = source_animal
+        .as_ref()
+        .map(|animal| animal.slaughterhouse_id)
+        .or_else(|| {
+            source_product
+                .as_ref()
+                .map(|product| product.slaughterhouse_id)
+        });
+    if matches!(source_slaughterhouse, Some(id) if id != payload.slaughterhouse_id) {
+        return Err(Message::invalid(
+            "slaughterhouse_id",
+            FieldErrorCode::InvalidValue,
+            "Waste source belongs to a different slaughterhouse",
+        ));
+    }
+
+    if let Some(product) = &source_product {
+        if !is_product_condemned(product) {
+            return Err(Message::error(
+                ErrorCode::InvalidState,
+                "Only condemned or disposed products can be recorded as waste",
+            )
+            .on("product", product.id));
+        }
+    }
+
+    // Material from condemned carcasses was not fit for consumption, so it cannot be Category 3
+    let condemned_source = matches!(&source_animal, Some(animal) if is_condemned(animal.id))
+        || source_product.is_some();
+    if condemned_source && payload.category == AbpCategory::Category3 {
+        return Err(Message::invalid(
+            "category",
+            FieldErrorCode::InvalidValue,
+            "Condemned material must be Category 1 or Category 2",
+        ));
+    }
+
+    let waste_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let record = WasteRecord {
+        id: waste_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        waste_type: payload.waste_type,
+        quantity: payload.quantity,
+        disposal_method,
+        disposal_date: time(),
+        handled_by: payload.handled_by,
+        cost: payload.cost,
+        category: Some(payload.category),
+        source_animal_id: payload.source_animal_id,
+        source_product_id: payload.source_product_id,
+    };
+
+    WASTE_RECORDS.with(|records| {
+        records.borrow_mut().insert(waste_id, record.clone());
+    });
+
+    // A condemned product leaves stock once it has been sent for disposal
+    if let Some(mut product) = source_product {
+        if product.status != ProductStatus::Disposed {
+            product.status = ProductStatus::Disposed;
+            MEAT_PRODUCTS.with(|products| {
+                products.borrow_mut().insert(product.id, product);
+            });
+        }
+    }
+
+    Ok(record)
+}
+
+fn disposal_routes(category: AbpCategory) -> &'static [&'static str] {
+    ABP_DISPOSAL_ROUTES
+        .iter()
+        .find(|(known, _)| *known == category)
+        .map(|(_, routes)| *routes)
+        .unwrap_or(&[])
+}
+
+fn disposal_route_permitted(category: AbpCategory, disposal_method: &str) -> bool {
+    disposal_routes(category).contains(&disposal_method)
+}
+
+// A product is condemned when it was disposed of, its carcass was condemned or it tested positive
+fn is_product_condemned(product: &MeatProduct) -> bool {
+    product.status == ProductStatus::Disposed
+        || is_condemned(product.animal_id)
+        || LAB_SAMPLES.with(|samples| {
+            samples.borrow().iter().any(|(_, sample)| {
+                sample.subject_type == LabSubjectType::Product
+                    && sample.subject_id == product.id
+                    && sample.result == LabResult::Positive
+            })
+        })
+}
+
+// Declare Veterinary Treatment for an animal awaiting slaughter
+#[ic_cdk::update]
+fn declare_veterinary_treatment(
+    payload: VeterinaryTreatmentPayload,
+) -> Result<VeterinaryTreatment, Message> {
+    store_veterinary_treatment(payload, time())
+}
+
+fn store_veterinary_treatment(
+    payload: VeterinaryTreatmentPayload,
+    now: u64,
+) -> Result<VeterinaryTreatment, Message> {
+    Validator::new()
+        .reference("animal_id", "animal", payload.animal_id)
+        .text("drug_name", &payload.drug_name, MAX_NAME_LENGTH)
+        .positive("dose", payload.dose)
+        .max_length("dose_unit", &payload.dose_unit, MAX_NAME_LENGTH)
+        .check(
+            payload.administration_date != 0 && payload.administration_date <= now,
+            "administration_date",
+            FieldErrorCode::OutOfRange,
+            "administration_date must be set and not in the future",
+        )
+        .check(
+            payload.withdrawal_days <= MAX_WITHDRAWAL_DAYS,
+            "withdrawal_days",
+            FieldErrorCode::OutOfRange,
+            format!("withdrawal_days cannot exceed {}", MAX_WITHDRAWAL_DAYS),
+        )
+        .text("declared_by", &payload.declared_by, MAX_NAME_LENGTH)
+        .finish()?;
+
+    // A withdrawal period only protects the food chain before slaughter
+    let animal = ANIMALS
+        .with(|animals| animals.borrow().get(&payload.animal_id))
+        .ok_or_else(|| Message::not_found("animal", payload.animal_id))?;
+    if animal.status != AnimalStatus::Received {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Cannot declare a treatment for a {} animal",
+                animal.status.as_str()
+            ),
+        )
+        .on("animal", animal.id));
+    }
+
+    let treatment_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let treatment = VeterinaryTreatment {
+        id: treatment_id,
+        animal_id: payload.animal_id,
+        drug_name: payload.drug_name,
+        dose: payload.dose,
+        dose_unit: payload.dose_unit,
+        administration_date: payload.administration_date,
+        withdrawal_days: payload.withdrawal_days,
+        withdrawal_end_date: withdrawal_end_date(
+            payload.administration_date,
+            payload.withdrawal_days,
+        ),
+        declared_by: payload.declared_by,
+        declared_at: now,
+    };
+
+    VETERINARY_TREATMENTS.with(|treatments| {
+        treatments
+            .borrow_mut()
+            .insert(treatment_id, treatment.clone());
+    });
+
+    Ok(treatment)
+}
+
+fn withdrawal_end_date(administration_date: u64, withdrawal_days: u32) -> u64 {
+    administration_date.saturating_add((withdrawal_days as u64).saturating_mul(NANOS_PER_DAY))
+}
+
+// Process Animal
+#[ic_cdk::update]
+fn process_animal(animal_id: u64) -> Result<Animal, Message> {
+    let mut animal = ANIMALS
+        .with(|animals| animals.borrow().get(&animal_id))
+        .ok_or_else(|| Message::not_found("animal", animal_id))?;
+
+    if animal.status != AnimalStatus::Received {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Animal cannot be processed in status {}",
+                animal.status.as_str()
+            ),
+        )
+        .on("animal", animal_id));
+    }
+
+    ensure_withdrawal_complete(animal_id, time())?;
+    ensure_not_locked_down(animal.slaughterhouse_id)?;
+
+    animal.status = AnimalStatus::Processed;
+    ANIMALS.with(|animals| {
+        animals.borrow_mut().insert(animal_id, animal.clone());
+    });
+
+    Ok(animal)
+}
+
+// Latest withdrawal end date across all treatments declared for the animal,
+// or 0 when nothing is declared
+fn earliest_slaughter_date(animal_id: u64) -> u64 {
+    VETERINARY_TREATMENTS.with(|treatments| {
+        treatments
+            .borrow()
+            .iter()
+            .filter(|(_, treatment)| treatment.animal_id == animal_id)
+            .map(|(_, treatment)| treatment.withdrawal_end_date)
+            .max()
+            .unwrap_or(0)
+    })
+}
+
+fn ensure_withdrawal_complete(animal_id: u64, now: u64) -> Result<(), Message> {
+    let earliest = earliest_slaughter_date(animal_id);
+    if earliest > now {
+        return Err(Message::error(
+            ErrorCode::Blocked,
+            format!(
+                "Animal {} is under a drug withdrawal period; earliest permitted slaughter date is {}",
+                animal_id, earliest
+            ),
+        )
+        .on("animal", animal_id));
+    }
+
+    Ok(())
+}
+
+#[ic_cdk::query]
+fn get_animal_treatments(animal_id: u64) -> Result<Vec<VeterinaryTreatment>, Message> {
+    let animal_exists = ANIMALS.with(|animals| animals.borrow().contains_key(&animal_id));
+    if !animal_exists {
+        return Err(Message::not_found("animal", animal_id));
+    }
+
+    Ok(VETERINARY_TREATMENTS.with(|treatments| {
+        treatments
+            .borrow()
+            .iter()
+            .filter(|(_, treatment)| treatment.animal_id == animal_id)
+            .map(|(_, treatment)| treatment)
+            .collect()
+    }))
+}
+
+#[ic_cdk::query]
+fn get_earliest_slaughter_date(animal_id: u64) -> Result<u64, Message> {
+    let animal_exists = ANIMALS.with(|animals| animals.borrow().contains_key(&animal_id));
+    if !animal_exists {
+        return Err(Message::not_found("animal", animal_id));
+    }
+
+    Ok(earliest_slaughter_date(animal_id).max(time()))
+}
+
+// Set Welfare Limits
+#[ic_cdk::update]
+fn set_welfare_limits(payload: WelfareLimitsPayload) -> Result<WelfareLimits, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .positive("max_transport_hours", payload.max_transport_hours)
+        .positive("max_lairage_hours", payload.max_lairage_hours)
+        .positive(
+            "feed_required_after_hours",
+            payload.feed_required_after_hours,
+        )
+        .finish()?;
+
+    let limits = WelfareLimits {
+        slaughterhouse_id: payload.slaughterhouse_id,
+        max_transport_hours: payload.max_transport_hours,
+        max_lairage_hours: payload.max_lairage_hours,
+        feed_required_after_hours: payload.feed_required_after_hours,
+        updated_at: time(),
+    };
+
+    WELFARE_LIMITS.with(|limits_map| {
+        limits_map
+            .borrow_mut()
+            .insert(payload.slaughterhouse_id, limits.clone());
+    });
+
+    Ok(limits)
+}
+
+// Configured limits, falling back to common regulatory defaults
+fn welfare_limits_for(slaughterhouse_id: u64) -> WelfareLimits {
+    WELFARE_LIMITS
+        .with(|limits| limits.borrow().get(&slaughterhouse_id))
+        .unwrap_or(WelfareLimits {
+            slaughterhouse_id,
+            max_transport_hours: 8.0,
+            max_lairage_hours: 72.0,
+            feed_required_after_hours: 12.0,
+            updated_at: 0,
+        })
+}
+
+// Record Welfare Event
+#[ic_cdk::update]
+fn record_welfare_event(payload: WelfareEventPayload) -> Result<WelfareEvent, Message> {
+    Validator::new()
+        .non_negative("duration_hours", payload.duration_hours)
+        .text("recorded_by", &payload.recorded_by, MAX_NAME_LENGTH)
+        .max_length("notes", &payload.notes, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let mut animal = ANIMALS
+        .with(|animals| animals.borrow().get(&payload.animal_id))
+        .ok_or_else(|| Message::not_found("animal", payload.animal_id))?;
+
+    // Events cover the animal between arrival and stunning, with one stun per animal
+    if animal.status != AnimalStatus::Received {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Cannot record welfare events for a {} animal",
+                animal.status.as_str()
+            ),
+        )
+        .on("animal", animal.id));
+    }
+    let stunned = has_welfare_event(animal.id, WelfareEventType::Stun);
+    match payload.event_type {
+        WelfareEventType::Stun if stunned => {
+            return Err(Message::error(
+                ErrorCode::AlreadyExists,
+                "Animal has already been stunned; record a re-stun instead",
+            )
+            .on("animal", animal.id));
+        }
+        WelfareEventType::ReStun if !stunned => {
+            return Err(Message::error(
+                ErrorCode::InvalidState,
+                "A re-stun requires a prior stun event",
+            )
+            .on("animal", animal.id));
+        }
+        _ => {}
+    }
+
+    let limits = welfare_limits_for(animal.slaughterhouse_id);
+    let event_time = time();
+    let mut duration_hours = payload.duration_hours;
+    let mut flag_reasons: Vec<String> = Vec::new();
+
+    match payload.event_type {
+        WelfareEventType::Transport if duration_hours > limits.max_transport_hours => {
+            flag_reasons.push(format!(
+                "Transport of {:.1}h exceeds limit of {:.1}h",
+                duration_hours, limits.max_transport_hours
+            ));
+        }
+        WelfareEventType::Stun => {
+            // Lairage runs from arrival until the animal is stunned
+            duration_hours =
+                event_time.saturating_sub(animal.arrival_time) as f64 / NANOS_PER_HOUR as f64;
+            if duration_hours > limits.max_lairage_hours {
+                flag_reasons.push(format!(
+                    "Lairage of {:.1}h exceeds limit of {:.1}h",
+                    duration_hours, limits.max_lairage_hours
+                ));
+            }
+            if duration_hours > limits.feed_required_after_hours
+                && !has_welfare_event(payload.animal_id, WelfareEventType::FeedAccess)
+            {
+                flag_reasons.push(format!(
+                    "No feed recorded during {:.1}h lairage",
+                    duration_hours
+                ));
+            }
+            if !payload.effective {
+                flag_reasons.push("Ineffective stun".to_string());
+            }
+        }
+        WelfareEventType::ReStun => flag_reasons.push("Re-stun required".to_string()),
+        WelfareEventType::DeadOnArrival => flag_reasons.push("Dead on arrival".to_string()),
+        _ => {}
+    }
+
+    let event_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let event = WelfareEvent {
+        id: event_id,
+        animal_id: payload.animal_id,
+        slaughterhouse_id: animal.slaughterhouse_id,
+        event_type: payload.event_type,
+        event_time,
+        duration_hours,
+        effective: payload.effective,
+        recorded_by: payload.recorded_by,
+        notes: payload.notes,
+        flagged: !flag_reasons.is_empty(),
+        flag_reason: flag_reasons.join("; "),
+    };
+
+    WELFARE_EVENTS.with(|events| {
+        events.borrow_mut().insert(event_id, event.clone());
+    });
+
+    if event.event_type == WelfareEventType::DeadOnArrival {
+        animal.status = AnimalStatus::Disposed;
+        ANIMALS.with(|animals| {
+            animals.borrow_mut().insert(animal.id, animal);
+        });
+    }
+
+    Ok(event)
+}
+
+fn has_welfare_event(animal_id: u64, event_type: WelfareEventType) -> bool {
+    WELFARE_EVENTS.with(|events| {
+        events
+            .borrow()
+            .iter()
+            .any(|(_, event)| event.animal_id == animal_id && event.event_type == event_type)
+    })
+}
+
+#[ic_cdk::query]
+fn get_animal_welfare_events(animal_id: u64) -> Result<Vec<WelfareEvent>, Message> {
+    let animal_exists = ANIMALS.with(|animals| animals.borrow().contains_key(&animal_id));
+    if !animal_exists {
+        return Err(Message::not_found("animal", animal_id));
+    }
+
+    Ok(WELFARE_EVENTS.with(|events| {
+        events
+            .borrow()
+            .iter()
+            .filter(|(_, event)| event.animal_id == animal_id)
+            .map(|(_, event)| event)
+            .collect()
+    }))
+}
+
+// Declare Lockdown, restricted to canister controllers
+#[ic_cdk::update]
+fn declare_lockdown(payload: LockdownPayload) -> Result<Lockdown, Message> {
+    let declared_by = caller();
+    if !is_controller(&declared_by) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a controller can declare a lockdown",
+        ));
+    }
+
+    Validator::new()
+        .check(
+            NOTIFIABLE_DISEASES.contains(&payload.disease.as_str()),
+            "disease",
+            FieldErrorCode::InvalidValue,
+            format!("{} is not a notifiable disease", payload.disease),
+        )
+        .text("reason", &payload.reason, MAX_NOTES_LENGTH)
+        .check(
+            payload.exposure_cutoff <= time(),
+            "exposure_cutoff",
+            FieldErrorCode::OutOfRange,
+            "exposure_cutoff cannot be in the future",
+        )
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .finish()?;
+
+    let suspected_animal_exists = ANIMALS.with(|animals| {
+        animals
+            .borrow()
+            .get(&payload.suspected_animal_id)
+            .map(|animal| animal.slaughterhouse_id == payload.slaughterhouse_id)
+            .unwrap_or(false)
+    });
+    if !suspected_animal_exists {
+        return Err(Message::not_found("animal", payload.suspected_animal_id));
+    }
+
+    if let Some(lockdown) = active_lockdown(payload.slaughterhouse_id) {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!("Slaughterhouse is already under lockdown {}", lockdown.id),
+        )
+        .on("lockdown", lockdown.id));
+    }
+
+    let lockdown_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let lockdown = Lockdown {
+        id: lockdown_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        disease: payload.disease,
+        suspected_animal_id: payload.suspected_animal_id,
+        exposure_cutoff: payload.exposure_cutoff,
+        reason: payload.reason,
+        declared_by: declared_by.to_text(),
+        declared_at: time(),
+        active: true,
+        lifted_by: "".to_string(),
+        lifted_at: 0,
+        lift_reason: "".to_string(),
+    };
+
+    LOCKDOWNS.with(|lockdowns| {
+        lockdowns.borrow_mut().insert(lockdown_id, lockdown.clone());
+    });
+
+    Ok(lockdown)
+}
+
+// Lift Lockdown, restricted to canister controllers
+#[ic_cdk::update]
+fn lift_lockdown(lockdown_id: u64, reason: String) -> Result<Lockdown, Message> {
+    let lifted_by = caller();
+    if !is_controller(&lifted_by) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a controller can lift a lockdown",
+        ));
+    }
+
+    Validator::new()
+        .text("reason", &reason, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let mut lockdown = LOCKDOWNS
+        .with(|lockdowns| lockdowns.borrow().get(&lockdown_id))
+        .ok_or_else(|| Message::not_found("lockdown", lockdown_id))?;
+
+    if !lockdown.active {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "Lockdown already lifted")
+                .on("lockdown", lockdown_id),
+        );
+    }
+
+    lockdown.active = false;
+    lockdown.lifted_by = lifted_by.to_text();
+    lockdown.lifted_at = time();
+    lockdown.lift_reason = reason;
+
+    LOCKDOWNS.with(|lockdowns| {
+        lockdowns.borrow_mut().insert(lockdown_id, lockdown.clone());
+    });
+
+    Ok(lockdown)
+}
+
+fn active_lockdown(slaughterhouse_id: u64) -> Option<Lockdown> {
+    LOCKDOWNS.with(|lockdowns| {
+        lockdowns
+            .borrow()
+            .iter()
+            .map(|(_, lockdown)| lockdown)
+            .find(|lockdown| lockdown.active && lockdown.slaughterhouse_id == slaughterhouse_id)
+    })
+}
+
+// Animals that arrived since the exposure cutoff, and the suspected animal, are quarantined
+fn is_animal_exposed(lockdown: &Lockdown, animal: &Animal) -> bool {
+    animal.slaughterhouse_id == lockdown.slaughterhouse_id
+        && (animal.arrival_time >= lockdown.exposure_cutoff
+            || animal.id == lockdown.suspected_animal_id)
+}
+
+// Products processed since the exposure cutoff, and those of the suspected animal, are quarantined
+fn is_product_exposed(lockdown: &Lockdown, product: &MeatProduct) -> bool {
+    product.slaughterhouse_id == lockdown.slaughterhouse_id
+        && (product.created_at >= lockdown.exposure_cutoff
+            || product.animal_id == lockdown.suspected_animal_id)
+}
+
+fn ensure_not_locked_down(slaughterhouse_id: u64) -> Result<(), Message> {
+    match active_lockdown(slaughterhouse_id) {
+        Some(lockdown) => Err(Message::error(
+            ErrorCode::Blocked,
+            format!(
+                "Slaughterhouse is under {} lockdown {}",
+                lockdown.disease, lockdown.id
+            ),
+        )
+        .on("lockdown", lockdown.id)),
+        None => Ok(()),
+    }
+}
+
+#[ic_cdk::query]
+fn get_active_lockdown(slaughterhouse_id: u64) -> Result<Lockdown, Message> {
+    active_lockdown(slaughterhouse_id).ok_or_else(|| {
+        Message::not_found_by(
+            "lockdown",
+            format!(
+                "Slaughterhouse {} has no active lockdown",
+                slaughterhouse_id
+            ),
+        )
+    })
+}
+
+#[ic_cdk::query]
+fn get_lockdown_exposure(lockdown_id: u64) -> Result<LockdownExposure, Message> {
+    let lockdown = LOCKDOWNS
+        .with(|lockdowns| lockdowns.borrow().get(&lockdown_id))
+        .ok_or_else(|| Message::not_found("lockdown", lockdown_id))?;
+
+    let quarantined_animals = ANIMALS.with(|animals| {
+        animals
+            .borrow()
+            .iter()
+            .filter(|(_, animal)| is_animal_exposed(&lockdown, animal))
+            .map(|(_, animal)| animal)
+            .collect()
+    });
+
+    let quarantined_products = MEAT_PRODUCTS.with(|products| {
+        products
+            .borrow()
+            .iter()
+            .filter(|(_, product)| is_product_exposed(&lockdown, product))
+            .map(|(_, product)| product)
+            .collect()
+    });
+
+    Ok(LockdownExposure {
+        lockdown,
+        quarantined_animals,
+        quarantined_products,
+    })
+}
+
+#[ic_cdk::query]
+fn validate_tag_number(tag_number: String) -> Result<TagValidation, Message> {
+    let validation = validate_tag(&tag_number)?;
+    ensure_tag_available(&validation.tag_number)?;
+    Ok(validation)
+}
+
+// Register Supplier
+#[ic_cdk::update]
+fn register_supplier(payload: SupplierPayload) -> Result<Supplier, Message> {
+    Validator::new()
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .text("contact", &payload.contact, MAX_NAME_LENGTH)
+        .max_length("email", &payload.email, MAX_NAME_LENGTH)
+        .max_length("supplier_type", &payload.supplier_type, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let supplier_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let supplier = Supplier {
+        id: supplier_id,
+        name: payload.name,
+        contact: payload.contact,
+        email: payload.email,
+        supplier_type: payload.supplier_type,
+        rating: 0,
+        active_since: time(),
+        last_supply_date: 0,
+    };
+
+    SUPPLIERS.with(|suppliers| {
+        suppliers.borrow_mut().insert(supplier_id, supplier.clone());
+    });
+
+    Ok(supplier)
+}
+
+// Create Consignment
+#[ic_cdk::update]
+fn create_consignment(payload: ConsignmentPayload) -> Result<Consignment, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("haulier", &payload.haulier, MAX_NAME_LENGTH)
+        .text("vehicle_plate", &payload.vehicle_plate, MAX_NAME_LENGTH)
+        .text(
+            "movement_permit_number",
+            &payload.movement_permit_number,
+            MAX_NAME_LENGTH,
+        )
+        .check(
+            payload.headcount_declared > 0,
+            "headcount_declared",
+            FieldErrorCode::OutOfRange,
+            "headcount_declared must be positive",
+        )
+        .check(
+            payload.arrival_time >= payload.departure_time,
+            "arrival_time",
+            FieldErrorCode::OutOfRange,
+            "arrival_time cannot precede departure_time",
+        )
+        .finish()?;
+
+    let mut supplier = SUPPLIERS
+        .with(|suppliers| suppliers.borrow().get(&payload.supplier_id))
+        .ok_or_else(|| Message::not_found("supplier", payload.supplier_id))?;
+
+    ensure_not_locked_down(payload.slaughterhouse_id)?;
+
+    let consignment_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let consignment = Consignment {
+        id: consignment_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        supplier_id: payload.supplier_id,
+        haulier: payload.haulier,
+        vehicle_plate: payload.vehicle_plate,
+        movement_permit_number: payload.movement_permit_number,
+        departure_time: payload.departure_time,
+        arrival_time: payload.arrival_time,
+        headcount_declared: payload.headcount_declared,
+        headcount_received: 0,
+        animal_ids: Vec::new(),
+        created_at: time(),
+    };
+
+    CONSIGNMENTS.with(|consignments| {
+        consignments
+            .borrow_mut()
+            .insert(consignment_id, consignment.clone());
+    });
+
+    supplier.last_supply_date = payload.arrival_time;
+    SUPPLIERS.with(|suppliers| {
+        suppliers.borrow_mut().insert(supplier.id, supplier);
+    });
+
+    Ok(consignment)
+}
+
+// Register Consignment Animals
+#[ic_cdk::update]
+fn register_consignment_animals(
+    payload: RegisterConsignmentAnimalsPayload,
+) -> Result<Vec<Animal>, Message> {
+    if payload.animals.is_empty() {
+        return Err(Message::invalid(
+            "animals",
+            FieldErrorCode::Required,
+            "No animals provided",
+        ));
+    }
+
+    let mut consignment = CONSIGNMENTS
+        .with(|consignments| consignments.borrow().get(&payload.consignment_id))
+        .ok_or_else(|| Message::not_found("consignment", payload.consignment_id))?;
+
+    ensure_not_locked_down(consignment.slaughterhouse_id)?;
+
+    if consignment.animal_ids.len() + payload.animals.len() > MAX_CONSIGNMENT_ANIMALS {
+        return Err(Message::invalid(
+            "animals",
+            FieldErrorCode::TooMany,
+            format!(
+                "A consignment cannot hold more than {} animals",
+                MAX_CONSIGNMENT_ANIMALS
+            ),
+        ));
+    }
+
+    // Validate the whole batch up front so a bad tag does not leave it half registered
+    let mut batch_tags: Vec<String> = Vec::new();
+    let mut validator = Validator::new();
+    for (index, animal) in payload.animals.iter().enumerate() {
+        validator
+            .text(
+                &format!("animals[{}].tag_number", index),
+                &animal.tag_number,
+                MAX_NAME_LENGTH,
+            )
+            .text(
+                &format!("animals[{}].species", index),
+                &animal.species,
+                MAX_NAME_LENGTH,
+            )
+            .positive(&format!("animals[{}].weight", index), animal.weight);
+    }
+    validator.finish()?;
+    for (index, animal) in payload.animals.iter().enumerate() {
+        validate_animal_species(&animal.species, animal.weight)?;
+        check_reading(animal.scale_id, "weight", consignment.slaughterhouse_id)?;
+        let tag_number = validate_tag(&animal.tag_number)?.tag_number;
+        ensure_tag_available(&tag_number)?;
+        if batch_tags.contains(&tag_number) {
+            return Err(Message::invalid(
+                &format!("animals[{}].tag_number", index),
+                FieldErrorCode::Duplicate,
+                format!(
+                    "Tag number {} appears more than once in the batch",
+                    tag_number
+                ),
+            ));
+        }
+        batch_tags.push(tag_number);
+    }
+
+    let mut registered = Vec::new();
+    for animal in payload.animals {
+        let animal = register_animal(RegisterAnimalPayload {
+            slaughterhouse_id: consignment.slaughterhouse_id,
+            tag_number: animal.tag_number,
+            species: animal.species,
+            weight: animal.weight,
+            scale_id: animal.scale_id,
+        })?;
+        consignment.animal_ids.push(animal.id);
+        registered.push(animal);
+    }
+
+    consignment.headcount_received = consignment.animal_ids.len() as u32;
+    CONSIGNMENTS.with(|consignments| {
+        consignments
+            .borrow_mut()
+            .insert(consignment.id, consignment);
+    });
+
+    Ok(registered)
+}
+
+#[ic_cdk::query]
+fn get_consignment(consignment_id: u64) -> Result<Consignment, Message> {
+    CONSIGNMENTS
+        .with(|consignments| consignments.borrow().get(&consignment_id))
+        .ok_or_else(|| Message::not_found("consignment", consignment_id))
+}
+
+#[ic_cdk::query]
+fn get_consignment_discrepancies(consignment_id: u64) -> Result<ConsignmentDiscrepancies, Message> {
+    let consignment = get_consignment(consignment_id)?;
+
+    let dead_on_arrival = consignment
+        .animal_ids
+        .iter()
+        .copied()
+        .filter(|animal_id| has_welfare_event(*animal_id, WelfareEventType::DeadOnArrival))
+        .collect();
+
+    Ok(ConsignmentDiscrepancies {
+        missing_animals: consignment
+            .headcount_declared
+            .saturating_sub(consignment.headcount_received),
+        surplus_animals: consignment
+            .headcount_received
+            .saturating_sub(consignment.headcount_declared),
+        dead_on_arrival,
+        transit_hours: (consignment.arrival_time - consignment.departure_time) as f64
+            / NANOS_PER_HOUR as f64,
+        consignment,
+    })
+}
+
+// Grade Carcass
+#[ic_cdk::update]
+fn grade_carcass(payload: CarcassGradePayload) -> Result<CarcassGrade, Message> {
+    Validator::new()
+        .text("graded_by", &payload.graded_by, MAX_NAME_LENGTH)
+        .positive("carcass_weight", payload.carcass_weight)
+        .finish()?;
+
+    let scheme = GRADING_SCHEMES
+        .iter()
+        .find(|scheme| scheme.name() == payload.scheme)
+        .ok_or_else(|| {
+            Message::invalid(
+                "scheme",
+                FieldErrorCode::InvalidValue,
+                format!("Unknown grading scheme {}", payload.scheme),
+            )
+        })?;
+
+    let animal = ANIMALS
+        .with(|animals| animals.borrow().get(&payload.animal_id))
+        .ok_or_else(|| Message::not_found("animal", payload.animal_id))?;
+
+    if animal.status != AnimalStatus::Processed {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            "Only processed carcasses can be graded",
+        )
+        .on("animal", animal.id));
+    }
+
+    if !scheme.accepts_species(&species_code(&animal.species)) {
+        return Err(Message::invalid(
+            "scheme",
+            FieldErrorCode::InvalidValue,
+            format!(
+                "{} grading does not apply to species {}",
+                scheme.name(),
+                animal.species
+            ),
+        ));
+    }
+
+    let already_graded = CARCASS_GRADES.with(|grades| {
+        grades
+            .borrow()
+            .iter()
+            .any(|(_, grade)| grade.animal_id == payload.animal_id)
+    });
+    if already_graded {
+        return Err(
+            Message::error(ErrorCode::AlreadyExists, "Carcass already graded")
+                .on("animal", animal.id),
+        );
+    }
+
+    let grade_label = scheme.grade(&payload)?;
+
+    let weighing = check_reading(payload.scale_id, "weight", animal.slaughterhouse_id)?;
+
+    let grade_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let grade = CarcassGrade {
+        id: grade_id,
+        animal_id: payload.animal_id,
+        slaughterhouse_id: animal.slaughterhouse_id,
+        scheme: payload.scheme,
+        carcass_weight: payload.carcass_weight,
+        conformation: payload.conformation,
+        fat_class: payload.fat_class,
+        quality_grade: payload.quality_grade,
+        yield_grade: payload.yield_grade,
+        lean_meat_percentage: payload.lean_meat_percentage,
+        grade: grade_label,
+        graded_by: payload.graded_by,
+        graded_at: time(),
+    };
+
+    CARCASS_GRADES.with(|grades| {
+        grades.borrow_mut().insert(grade_id, grade.clone());
+    });
+    record_reading(weighing, "carcass_grade", grade_id, grade.carcass_weight);
+
+    Ok(grade)
+}
+
+#[ic_cdk::query]
+fn get_carcass_grade(animal_id: u64) -> Result<CarcassGrade, Message> {
+    CARCASS_GRADES
+        .with(|grades| {
+            grades
+                .borrow()
+                .iter()
+                .map(|(_, grade)| grade)
+                .find(|grade| grade.animal_id == animal_id)
+        })
+        .ok_or_else(|| {
+            Message::not_found_by(
+                "carcass_grade",
+                format!("Animal {} has no carcass grade", animal_id),
+            )
+        })
+}
+
+// Supplier of the consignment the animal arrived in, if any
+fn animal_supplier_id(animal_id: u64) -> Option<u64> {
+    CONSIGNMENTS.with(|consignments| {
+        consignments
+            .borrow()
+            .iter()
+            .find(|(_, consignment)| consignment.animal_ids.contains(&animal_id))
+            .map(|(_, consignment)| consignment.supplier_id)
+    })
+}
+
+// Create Price Grid
+#[ic_cdk::update]
+fn create_price_grid(payload: PriceGridPayload) -> Result<PriceGrid, Message> {
+    let mut validator = Validator::new();
+    validator
+        .positive("base_price_per_kg", payload.base_price_per_kg)
+        .check(
+            payload.valid_to > payload.valid_from,
+            "valid_to",
+            FieldErrorCode::OutOfRange,
+            "Price grid validity period is empty",
+        )
+        .check(
+            GRADING_SCHEMES
+                .iter()
+                .any(|scheme| scheme.name() == payload.scheme),
+            "scheme",
+            FieldErrorCode::InvalidValue,
+            format!("Unknown grading scheme {}", payload.scheme),
+        )
+        .check(
+            payload.adjustments.len() <= MAX_GRID_ADJUSTMENTS,
+            "adjustments",
+            FieldErrorCode::TooMany,
+            format!("At most {} adjustments are allowed", MAX_GRID_ADJUSTMENTS),
+        );
+    for (index, adjustment) in payload.adjustments.iter().enumerate() {
+        validator
+            .text(
+                &format!("adjustments[{}].grade", index),
+                &adjustment.grade,
+                MAX_NAME_LENGTH,
+            )
+            .range(
+                &format!("adjustments[{}].adjustment_per_kg", index),
+                adjustment.adjustment_per_kg,
+                -payload.base_price_per_kg,
+                f64::MAX,
+            );
+    }
+    validator.finish()?;
+
+    let species =
+        find_species(&payload.species).ok_or_else(|| unknown_species(&payload.species))?;
+
+    let grid_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let grid = PriceGrid {
+        id: grid_id,
+        species: species.code,
+        scheme: payload.scheme,
+        valid_from: payload.valid_from,
+        valid_to: payload.valid_to,
+        base_price_per_kg: payload.base_price_per_kg,
+        adjustments: payload.adjustments,
+        created_at: time(),
+    };
+
+    PRICE_GRIDS.with(|grids| {
+        grids.borrow_mut().insert(grid_id, grid.clone());
+    });
+
+    Ok(grid)
+}
+
+// Most recently created grid covering the species, scheme and date
+fn find_price_grid(species: &str, scheme: &str, date: u64) -> Option<PriceGrid> {
+    PRICE_GRIDS.with(|grids| {
+        grids
+            .borrow()
+            .iter()
+            .map(|(_, grid)| grid)
+            .filter(|grid| {
+                grid.species == species_code(species)
+                    && grid.scheme == scheme
+                    && grid.valid_from <= date
+                    && date < grid.valid_to
+            })
+            .max_by_key(|grid| grid.created_at)
+    })
+}
+
+// A carcass is condemned when it failed a quality inspection or tested positive
+fn is_condemned(animal_id: u64) -> bool {
+    let failed_inspection = QUALITY_INSPECTIONS.with(|inspections| {
+        inspections
+            .borrow()
+            .iter()
+            .any(|(_, inspection)| inspection.animal_id == animal_id && !inspection.passed)
+    });
+
+    failed_inspection
+        || LAB_SAMPLES.with(|samples| {
+            samples.borrow().iter().any(|(_, sample)| {
+                sample.subject_type == LabSubjectType::Animal
+                    && sample.subject_id == animal_id
+                    && sample.result == LabResult::Positive
+            })
+        })
+}
+
+// Generate Settlement
+#[ic_cdk::update]
+fn generate_settlement(payload: SettlementPayload) -> Result<SettlementDetails, Message> {
+    let mut validator = Validator::new();
+    validator.check(
+        payload.deductions.len() <= MAX_SETTLEMENT_DEDUCTIONS,
+        "deductions",
+        FieldErrorCode::TooMany,
+        format!(
+            "At most {} deductions are allowed",
+            MAX_SETTLEMENT_DEDUCTIONS
+        ),
+    );
+    for (index, deduction) in payload.deductions.iter().enumerate() {
+        validator
+            .text(
+                &format!("deductions[{}].description", index),
+                &deduction.description,
+                MAX_NAME_LENGTH,
+            )
+            .positive(&format!("deductions[{}].amount", index), deduction.amount);
+    }
+    validator.finish()?;
+
+    let consignment = get_consignment(payload.consignment_id)?;
+    if consignment.animal_ids.is_empty() {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            "Consignment has no registered animals",
+        )
+        .on("consignment", consignment.id));
+    }
+
+    let already_settled = SETTLEMENTS.with(|settlements| {
+        settlements
+            .borrow()
+            .iter()
+            .any(|(_, settlement)| settlement.consignment_id == payload.consignment_id)
+    });
+    if already_settled {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            "Consignment already has a settlement statement",
+        )
+        .on("consignment", consignment.id));
+    }
+
+    // Price every animal before storing anything
+    let mut lines = Vec::new();
+    for animal_id in &consignment.animal_ids {
+        let animal = ANIMALS
+            .with(|animals| animals.borrow().get(animal_id))
+            .ok_or_else(|| Message::not_found("animal", *animal_id))?;
+
+        if animal.status == AnimalStatus::Disposed || is_condemned(animal.id) {
+            lines.push(SettlementLine {
+                animal_id: animal.id,
+                tag_number: animal.tag_number,
+                condemned: true,
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let grade = get_carcass_grade(animal.id).map_err(|_| {
+            Message::error(
+                ErrorCode::InvalidState,
+                format!("Animal {} has not been graded yet", animal.id),
+            )
+            .on("animal", animal.id)
+        })?;
+        let grid =
+            find_price_grid(&animal.species, &grade.scheme, grade.graded_at).ok_or_else(|| {
+                Message::not_found_by(
+                    "price_grid",
+                    format!(
+                        "No {} price grid for {} on the grading date",
+                        grade.scheme, animal.species
+                    ),
+                )
+            })?;
+        let adjustment = grid
+            .adjustments
+            .iter()
+            .find(|adjustment| adjustment.grade == grade.grade)
+            .map(|adjustment| adjustment.adjustment_per_kg)
+            .unwrap_or(0.0);
+        let price_per_kg = (grid.base_price_per_kg + adjustment).max(0.0);
+
+        lines.push(SettlementLine {
+            animal_id: animal.id,
+            tag_number: animal.tag_number,
+            grade: grade.grade,
+            carcass_weight: grade.carcass_weight,
+            price_per_kg,
+            amount: grade.carcass_weight * price_per_kg,
+            condemned: false,
+            ..Default::default()
+        });
+    }
+
+    let settlement_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    for line in lines.iter_mut() {
+        line.id = ID_COUNTER.with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter
+                .borrow_mut()
+                .set(current_value + 1)
+                .expect("Counter increment failed");
+            current_value
+        });
+        line.settlement_id = settlement_id;
+        SETTLEMENT_LINES.with(|settlement_lines| {
+            settlement_lines.borrow_mut().insert(line.id, line.clone());
+        });
+    }
+
+    let gross_amount: f64 = lines.iter().map(|line| line.amount).sum();
+    let total_deductions: f64 = payload
+        .deductions
+        .iter()
+        .map(|deduction| deduction.amount)
+        .sum();
+    let now = time();
+
+    let statement = SettlementStatement {
+        id: settlement_id,
+        consignment_id: consignment.id,
+        supplier_id: consignment.supplier_id,
+        slaughterhouse_id: consignment.slaughterhouse_id,
+        animals_paid: lines.iter().filter(|line| !line.condemned).count() as u32,
+        animals_condemned: lines.iter().filter(|line| line.condemned).count() as u32,
+        total_carcass_weight: lines.iter().map(|line| line.carcass_weight).sum(),
+        gross_amount,
+        deductions: payload.deductions,
+        total_deductions,
+        net_amount: gross_amount - total_deductions,
+        status: SettlementStatus::Pending,
+        created_at: now,
+        status_updated_at: now,
+    };
+
+    SETTLEMENTS.with(|settlements| {
+        settlements
+            .borrow_mut()
+            .insert(settlement_id, statement.clone());
+    });
+
+    Ok(SettlementDetails { statement, lines })
+}
+
+// Update Settlement Status
+#[ic_cdk::update]
+fn update_settlement_status(
+    settlement_id: u64,
+    status: SettlementStatus,
+) -> Result<SettlementStatement, Message> {
+    let mut statement = SETTLEMENTS
+        .with(|settlements| settlements.borrow().get(&settlement_id))
+        .ok_or_else(|| Message::not_found("settlement", settlement_id))?;
+
+    let allowed = SETTLEMENT_TRANSITIONS
+        .iter()
+        .find(|(from, _)| *from == statement.status)
+        .map(|(_, to)| to.contains(&status))
+        .unwrap_or(false);
+    if !allowed {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Settlement cannot move from {} to {}",
+                statement.status.as_str(),
+                status.as_str()
+            ),
+        )
+        .on("settlement", settlement_id));
+    }
+
+    statement.status = status;
+    statement.status_updated_at = time();
+
+    SETTLEMENTS.with(|settlements| {
+        settlements
+            .borrow_mut()
+            .insert(settlement_id, statement.clone());
+    });
+
+    Ok(statement)
+}
+
+#[ic_cdk::query]
+fn get_settlement(settlement_id: u64) -> Result<SettlementDetails, Message> {
+    let statement = SETTLEMENTS
+        .with(|settlements| settlements.borrow().get(&settlement_id))
+        .ok_or_else(|| Message::not_found("settlement", settlement_id))?;
+
+    let lines = SETTLEMENT_LINES.with(|settlement_lines| {
+        settlement_lines
+            .borrow()
+            .iter()
+            .filter(|(_, line)| line.settlement_id == settlement_id)
+            .map(|(_, line)| line)
+            .collect()
+    });
+
+    Ok(SettlementDetails { statement, lines })
+}
+
+#[ic_cdk::query]
+fn get_supplier_settlements(supplier_id: u64) -> Result<Vec<SettlementStatement>, Message> {
+    let supplier_exists = SUPPLIERS.with(|suppliers| suppliers.borrow().contains_key(&supplier_id));
+    if !supplier_exists {
+        return Err(Message::not_found("supplier", supplier_id));
+    }
+
+    Ok(SETTLEMENTS.with(|settlements| {
+        settlements
+            .borrow()
+            .iter()
+            .filter(|(_, settlement)| settlement.supplier_id == supplier_id)
+            .map(|(_, settlement)| settlement)
+            .collect()
+    }))
+}
+
+// Register Species
+#[ic_cdk::update]
+fn register_species(payload: SpeciesPayload) -> Result<Species, Message> {
+    let code = payload.code.trim().to_lowercase();
+    if find_species(&code).is_some() {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!("Species {} already exists", code),
+        ));
+    }
+
+    store_species(None, payload)
+}
+
+// Update Species
+#[ic_cdk::update]
+fn update_species(payload: SpeciesPayload) -> Result<Species, Message> {
+    let existing = find_species(&payload.code)
+        .filter(|species| species.code == payload.code.trim().to_lowercase())
+        .ok_or_else(|| unknown_species(&payload.code))?;
+
+    store_species(Some(existing.id), payload)
+}
+
+// Validates and stores a species; new species are given an id once they are valid
+fn store_species(species_id: Option<u64>, payload: SpeciesPayload) -> Result<Species, Message> {
+    let mut validator = Validator::new();
+    validator
+        .text("code", &payload.code, MAX_NAME_LENGTH)
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .positive("min_weight", payload.min_weight)
+        .bounds(
+            "weight",
+            payload.min_weight,
+            payload.max_weight,
+            (0.0, f64::MAX),
+        )
+        .bounds(
+            "temperature",
+            payload.min_temperature,
+            payload.max_temperature,
+            TEMPERATURE_RANGE,
+        )
+        .bounds("ph", payload.min_ph, payload.max_ph, PH_RANGE)
+        .check(
+            payload.expected_dressing_percentage > 0.0
+                && payload.expected_dressing_percentage <= 100.0,
+            "expected_dressing_percentage",
+            FieldErrorCode::OutOfRange,
+            "expected_dressing_percentage must be within 0-100",
+        )
+        .check(
+            payload.shelf_life_days > 0,
+            "shelf_life_days",
+            FieldErrorCode::OutOfRange,
+            "shelf_life_days must be positive",
+        )
+        .check(
+            payload.aliases.len() <= MAX_SPECIES_LIST_ENTRIES,
+            "aliases",
+            FieldErrorCode::TooMany,
+            format!("At most {} aliases are allowed", MAX_SPECIES_LIST_ENTRIES),
+        )
+        .check(
+            payload.allowed_product_types.len() <= MAX_SPECIES_LIST_ENTRIES,
+            "allowed_product_types",
+            FieldErrorCode::TooMany,
+            format!(
+                "At most {} product types are allowed",
+                MAX_SPECIES_LIST_ENTRIES
+            ),
+        );
+
+    let code = payload.code.trim().to_lowercase();
+    let aliases: Vec<String> = payload
+        .aliases
+        .iter()
+        .map(|alias| alias.trim().to_lowercase())
+        .collect();
+
+    let allowed_product_types: Vec<String> = payload
+        .allowed_product_types
+        .iter()
+        .map(|product_type| product_type.trim().to_lowercase())
+        .collect();
+    for (index, product_type) in allowed_product_types.iter().enumerate() {
+        validator.text(
+            &format!("allowed_product_types[{}]", index),
+            product_type,
+            MAX_NAME_LENGTH,
+        );
+        validator.check(
+            !allowed_product_types[..index].contains(product_type),
+            &format!("allowed_product_types[{}]", index),
+            FieldErrorCode::Duplicate,
+            format!("Duplicate product type {}", product_type),
+        );
+    }
+
+    // Codes and aliases must resolve to exactly one species
+    for (index, alias) in aliases.iter().enumerate() {
+        validator
+            .text(&format!("aliases[{}]", index), alias, MAX_NAME_LENGTH)
+            .check(
+                *alias != code && !aliases[..index].contains(alias),
+                &format!("aliases[{}]", index),
+                FieldErrorCode::Duplicate,
+                format!("Duplicate alias {}", alias),
+            );
+        if let Some(other) = find_species(alias) {
+            validator.check(
+                Some(other.id) == species_id,
+                &format!("aliases[{}]", index),
+                FieldErrorCode::Duplicate,
+                format!("Alias {} already belongs to species {}", alias, other.code),
+            );
+        }
+    }
+    validator.finish()?;
+
+    let species_id = species_id.unwrap_or_else(|| {
+        ID_COUNTER.with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter
+                .borrow_mut()
+                .set(current_value + 1)
+                .expect("Counter increment failed");
+            current_value
+        })
+    });
+    let species = Species {
+        id: species_id,
+
+        code,
+        name: payload.name,
+        aliases,
+        min_weight: payload.min_weight,
+        max_weight: payload.max_weight,
+        expected_dressing_percentage: payload.expected_dressing_percentage,
+        allowed_product_types,
+
+        min_temperature: payload.min_temperature,
+        max_temperature: payload.max_temperature,
+        min_ph: payload.min_ph,
+        max_ph: payload.max_ph,
+        shelf_life_days: payload.shelf_life_days,
+        updated_at: time(),
+    };
+
+    SPECIES_CATALOG.with(|catalog| {
+        catalog.borrow_mut().insert(species_id, species.clone());
+    });
+
+    Ok(species)
+}
+
+#[ic_cdk::query]
+fn get_species_catalog() -> Vec<Species> {
+    SPECIES_CATALOG.with(|catalog| {
+        catalog
+            .borrow()
+            .iter()
+            .map(|(_, species)| species)
+            .collect()
+    })
+}
+
+// Take Lab Sample
+#[ic_cdk::update]
+fn take_lab_sample(payload: LabSamplePayload) -> Result<LabSample, Message> {
+    Validator::new()
+        .text("analyte", &payload.analyte, MAX_NAME_LENGTH)
+        .non_negative("limit_value", payload.limit_value)
+        .max_length("unit", &payload.unit, MAX_NAME_LENGTH)
+        .text("taken_by", &payload.taken_by, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let slaughterhouse_id = match payload.subject_type {
+        LabSubjectType::Animal => ANIMALS
+            .with(|animals| animals.borrow().get(&payload.subject_id))
+            .map(|animal| animal.slaughterhouse_id)
+            .ok_or_else(|| Message::not_found("animal", payload.subject_id))?,
+        LabSubjectType::Product => MEAT_PRODUCTS
+            .with(|products| products.borrow().get(&payload.subject_id))
+            .map(|product| product.slaughterhouse_id)
+            .ok_or_else(|| Message::not_found("product", payload.subject_id))?,
+    };
+
+    let sample_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let sample = LabSample {
+        id: sample_id,
+        subject_type: payload.subject_type,
+        subject_id: payload.subject_id,
+        slaughterhouse_id,
+        test_type: payload.test_type,
+        analyte: payload.analyte,
+        limit_value: payload.limit_value,
+        unit: payload.unit,
+        lab_name: "".to_string(),
+        status: LabSampleStatus::Taken,
+        custody_log: vec![CustodyEvent {
+            status: LabSampleStatus::Taken,
+            timestamp: time(),
+            handled_by: payload.taken_by,
+        }],
+        result_value: 0.0,
+        result: LabResult::Pending,
+        lab_reference: "".to_string(),
+        resulted_at: 0,
+    };
+
+    LAB_SAMPLES.with(|samples| {
+        samples.borrow_mut().insert(sample_id, sample.clone());
+    });
+
+    Ok(sample)
+}
+
+// Moves a sample to the next custody status, checking it is in the expected one
+fn advance_sample_custody(
+    sample_id: u64,
+    expected_status: LabSampleStatus,
+    next_status: LabSampleStatus,
+    handled_by: String,
+) -> Result<LabSample, Message> {
+    Validator::new()
+        .text("handled_by", &handled_by, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let mut sample = LAB_SAMPLES
+        .with(|samples| samples.borrow().get(&sample_id))
+        .ok_or_else(|| Message::not_found("lab_sample", sample_id))?;
+
+    if sample.status != expected_status {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Sample is {}, expected {}",
+                sample.status.as_str(),
+                expected_status.as_str()
+            ),
+        )
+        .on("lab_sample", sample_id));
+    }
+
+    sample.status = next_status;
+    sample.custody_log.push(CustodyEvent {
+        status: next_status,
+        timestamp: time(),
+        handled_by,
+    });
+
+    Ok(sample)
+}
+
+// Send Lab Sample
+#[ic_cdk::update]
+fn send_lab_sample(
+    sample_id: u64,
+    lab_name: String,
+    handled_by: String,
+) -> Result<LabSample, Message> {
+    Validator::new()
+        .text("lab_name", &lab_name, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let mut sample = advance_sample_custody(
+        sample_id,
+        LabSampleStatus::Taken,
+        LabSampleStatus::Sent,
+        handled_by,
+    )?;
+    sample.lab_name = lab_name;
+
+    LAB_SAMPLES.with(|samples| {
+        samples.borrow_mut().insert(sample_id, sample.clone());
+    });
+
+    Ok(sample)
+}
+
+// Receive Lab Sample, called by the laboratory
+#[ic_cdk::update]
+fn receive_lab_sample(sample_id: u64, handled_by: String) -> Result<LabSample, Message> {
+    let sample = advance_sample_custody(
+        sample_id,
+        LabSampleStatus::Sent,
+        LabSampleStatus::Received,
+        handled_by,
+    )?;
+
+    LAB_SAMPLES.with(|samples| {
+        samples.borrow_mut().insert(sample_id, sample.clone());
+    });
+
+    Ok(sample)
+}
+
+// Record Lab Result, called by the laboratory
+#[ic_cdk::update]
+fn record_lab_result(payload: LabResultPayload) -> Result<LabSample, Message> {
+    Validator::new()
+        .non_negative("result_value", payload.result_value)
+        .text("lab_reference", &payload.lab_reference, MAX_NAME_LENGTH)
+        .max_length("analyst", &payload.analyst, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let mut sample = advance_sample_custody(
+        payload.sample_id,
+        LabSampleStatus::Received,
+        LabSampleStatus::Resulted,
+        payload.analyst,
+    )?;
+    sample.result_value = payload.result_value;
+    sample.result = if payload.result_value > sample.limit_value {
+        LabResult::Positive
+    } else {
+        LabResult::Negative
+    };
+    sample.lab_reference = payload.lab_reference;
+    sample.resulted_at = time();
+
+    LAB_SAMPLES.with(|samples| {
+        samples
+            .borrow_mut()
+            .insert(payload.sample_id, sample.clone());
+    });
+
+    Ok(sample)
+}
+
+// First sample holding the product, either taken from it or from its animal,
+// whose result is pending or positive
+fn product_hold(product: &MeatProduct) -> Option<(u64, String)> {
+    LAB_SAMPLES.with(|samples| {
+        samples
+            .borrow()
+            .iter()
+            .map(|(_, sample)| sample)
+            .find(|sample| {
+                sample.result != LabResult::Negative
+                    && ((sample.subject_type == LabSubjectType::Product
+                        && sample.subject_id == product.id)
+                        || (sample.subject_type == LabSubjectType::Animal
+                            && sample.subject_id == product.animal_id))
+            })
+            .map(|sample| {
+                let reason = format!(
+                    "{} {} result {}",
+                    sample.test_type.as_str(),
+                    sample.analyte,
+                    sample.result.as_str()
+                );
+                (sample.id, reason)
+            })
+    })
+}
+
+#[ic_cdk::query]
+fn get_lab_samples(subject_type: LabSubjectType, subject_id: u64) -> Vec<LabSample> {
+    LAB_SAMPLES.with(|samples| {
+        samples
+            .borrow()
+            .iter()
+            .filter(|(_, sample)| {
+                sample.subject_type == subject_type && sample.subject_id == subject_id
+            })
+            .map(|(_, sample)| sample)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_held_products(slaughterhouse_id: u64) -> Result<Vec<HeldProduct>, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
+
+    Ok(MEAT_PRODUCTS.with(|products| {
+        products
+            .borrow()
+            .iter()
+            .filter(|(_, product)| product.slaughterhouse_id == slaughterhouse_id)
+            .filter_map(|(_, product)| {
+                product_hold(&product).map(|(sample_id, reason)| HeldProduct {
+                    product,
+                    sample_id,
+                    reason,
+                })
+            })
+            .collect()
+    }))
+}
+
+// Define Critical Control Point
+#[ic_cdk::update]
+fn define_ccp(payload: CriticalControlPointPayload) -> Result<CriticalControlPoint, Message> {
+    store_ccp(None, payload)
+}
+
+// Update Critical Control Point limits and monitoring frequency
+#[ic_cdk::update]
+fn update_ccp(
+    ccp_id: u64,
+    payload: CriticalControlPointPayload,
+) -> Result<CriticalControlPoint, Message> {
+    let existing = CRITICAL_CONTROL_POINTS
+        .with(|ccps| ccps.borrow().get(&ccp_id))
+        .ok_or_else(|| Message::not_found("critical_control_point", ccp_id))?;
+
+    if existing.slaughterhouse_id != payload.slaughterhouse_id {
+        return Err(Message::invalid(
+            "slaughterhouse_id",
+            FieldErrorCode::InvalidValue,
+            "A critical control point cannot move to another slaughterhouse",
+        ));
+    }
+
+    store_ccp(Some(existing), payload)
+}
+
+// Validates and stores a point; new points are given an id once they are valid
+fn store_ccp(
+    existing: Option<CriticalControlPoint>,
+    payload: CriticalControlPointPayload,
+) -> Result<CriticalControlPoint, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .text("hazard", &payload.hazard, MAX_NAME_LENGTH)
+        .text("parameter", &payload.parameter, MAX_NAME_LENGTH)
+        .max_length("unit", &payload.unit, MAX_NAME_LENGTH)
+        .check(
+            (1..=MAX_MONITORING_INTERVAL_MINUTES).contains(&payload.monitoring_interval_minutes),
+            "monitoring_interval_minutes",
+            FieldErrorCode::OutOfRange,
+            format!(
+                "monitoring_interval_minutes must be between 1 and {}",
+                MAX_MONITORING_INTERVAL_MINUTES
+            ),
+        )
+        .bounds(
+            "limit",
+            payload.min_limit,
+            payload.max_limit,
+            (f64::MIN, f64::MAX),
+        )
+        .finish()?;
+
+    let now = time();
+    let (ccp_id, created_at) = match existing {
+        Some(existing) => (existing.id, existing.created_at),
+        None => {
+            let ccp_id = ID_COUNTER.with(|counter| {
+                let current_value = *counter.borrow().get();
+                counter
+                    .borrow_mut()
+                    .set(current_value + 1)
+                    .expect("Counter increment failed");
+                current_value
+            });
+            (ccp_id, now)
+        }
+    };
+    let ccp = CriticalControlPoint {
+        id: ccp_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        name: payload.name,
+        hazard: payload.hazard,
+        parameter: payload.parameter,
+        unit: payload.unit,
+        min_limit: payload.min_limit,
+        max_limit: payload.max_limit,
+        monitoring_interval_minutes: payload.monitoring_interval_minutes,
+        created_at,
+        updated_at: now,
+    };
+
+    CRITICAL_CONTROL_POINTS.with(|ccps| {
+        ccps.borrow_mut().insert(ccp_id, ccp.clone());
+    });
+
+    Ok(ccp)
+}
+
+// Record CCP Monitoring; a reading outside the critical limits opens a deviation
+#[ic_cdk::update]
+fn record_ccp_monitoring(payload: CcpMonitoringPayload) -> Result<CcpMonitoringLog, Message> {
+    Validator::new()
+        .range("value", payload.value, f64::MIN, f64::MAX)
+        .text("recorded_by", &payload.recorded_by, MAX_NAME_LENGTH)
+        .max_length("notes", &payload.notes, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let ccp = CRITICAL_CONTROL_POINTS
+        .with(|ccps| ccps.borrow().get(&payload.ccp_id))
+        .ok_or_else(|| Message::not_found("critical_control_point", payload.ccp_id))?;
+
+    let limit_breached = if payload.value < ccp.min_limit {
+        Some("min")
+    } else if payload.value > ccp.max_limit {
+        Some("max")
+    } else {
+        None
+    };
+
+    let log_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+    let now = time();
+
+    let deviation_id = limit_breached.map(|limit| {
+        let deviation_id = ID_COUNTER.with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter
+                .borrow_mut()
+                .set(current_value + 1)
+                .expect("Counter increment failed");
+            current_value
+        });
+
+        let deviation = CcpDeviation {
+            id: deviation_id,
+            ccp_id: ccp.id,
+            slaughterhouse_id: ccp.slaughterhouse_id,
+            log_id,
+            value: payload.value,
+            limit_breached: limit.to_string(),
+            detected_at: now,
+            status: DeviationStatus::Open,
+            corrective_action_id: None,
+            closed_at: 0,
+        };
+        CCP_DEVIATIONS.with(|deviations| {
+            deviations.borrow_mut().insert(deviation_id, deviation);
+        });
+        deviation_id
+    });
+
+    let log = CcpMonitoringLog {
+        id: log_id,
+        ccp_id: ccp.id,
+        slaughterhouse_id: ccp.slaughterhouse_id,
+        value: payload.value,
+        within_limits: deviation_id.is_none(),
+        deviation_id,
+        recorded_by: payload.recorded_by,
+        recorded_at: now,
+        notes: payload.notes,
+    };
+
+    CCP_MONITORING_LOGS.with(|logs| {
+        logs.borrow_mut().insert(log_id, log.clone());
+    });
+
+    Ok(log)
+}
+
+// Record Corrective Action, the only way a deviation is closed
+#[ic_cdk::update]
+fn record_corrective_action(payload: CorrectiveActionPayload) -> Result<CcpDeviation, Message> {
+    Validator::new()
+        .text("action_taken", &payload.action_taken, MAX_NOTES_LENGTH)
+        .text(
+            "product_disposition",
+            &payload.product_disposition,
+            MAX_NAME_LENGTH,
+        )
+        .max_length(
+            "preventive_measure",
+            &payload.preventive_measure,
+            MAX_NOTES_LENGTH,
+        )
+        .text("performed_by", &payload.performed_by, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let mut deviation = CCP_DEVIATIONS
+        .with(|deviations| deviations.borrow().get(&payload.deviation_id))
+        .ok_or_else(|| Message::not_found("ccp_deviation", payload.deviation_id))?;
+
+    if deviation.status != DeviationStatus::Open {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "Deviation is already closed")
+                .on("ccp_deviation", deviation.id),
+        );
+    }
+
+    let action_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+    let now = time();
+
+    let action = CorrectiveAction {
+        id: action_id,
+        deviation_id: deviation.id,
+        action_taken: payload.action_taken,
+        product_disposition: payload.product_disposition,
+        preventive_measure: payload.preventive_measure,
+        performed_by: payload.performed_by,
+        recorded_at: now,
+    };
+
+    deviation.status = DeviationStatus::Closed;
+    deviation.corrective_action_id = Some(action_id);
+    deviation.closed_at = now;
+
+    CORRECTIVE_ACTIONS.with(|actions| {
+        actions.borrow_mut().insert(action_id, action);
+    });
+    CCP_DEVIATIONS.with(|deviations| {
+        deviations
+            .borrow_mut()
+            .insert(deviation.id, deviation.clone());
+    });
+
+    Ok(deviation)
+}
+
+#[ic_cdk::query]
+fn get_ccp_logs(ccp_id: u64) -> Result<Vec<CcpMonitoringLog>, Message> {
+    let ccp_exists = CRITICAL_CONTROL_POINTS.with(|ccps| ccps.borrow().contains_key(&ccp_id));
+    if !ccp_exists {
+        return Err(Message::not_found("critical_control_point", ccp_id));
+    }
+
+    Ok(CCP_MONITORING_LOGS.with(|logs| {
+        logs.borrow()
+            .iter()
+            .filter(|(_, log)| log.ccp_id == ccp_id)
+            .map(|(_, log)| log)
+            .collect()
+    }))
+}
+
+#[ic_cdk::query]
+fn get_open_deviations(slaughterhouse_id: u64) -> Vec<CcpDeviation> {
+    CCP_DEVIATIONS.with(|deviations| {
+        deviations
+            .borrow()
+            .iter()
+            .filter(|(_, deviation)| {
+                deviation.slaughterhouse_id == slaughterhouse_id
+                    && deviation.status == DeviationStatus::Open
+            })
+            .map(|(_, deviation)| deviation)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_corrective_action(action_id: u64) -> Result<CorrectiveAction, Message> {
+    CORRECTIVE_ACTIONS
+        .with(|actions| actions.borrow().get(&action_id))
+        .ok_or_else(|| Message::not_found("corrective_action", action_id))
+}
+
+// Raise Non-Conformance Report
+#[ic_cdk::update]
+fn raise_non_conformance(payload: NonConformancePayload) -> Result<NonConformanceReport, Message> {
+    Validator::new()
+        .text("description", &payload.description, MAX_NCR_TEXT_LENGTH)
+        .text("raised_by", &payload.raised_by, MAX_NAME_LENGTH)
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .finish()?;
+
+    if let Some(link) = &payload.link {
+        let linked_slaughterhouse = linked_record_slaughterhouse(link.linked_type, link.linked_id)?;
+        if matches!(linked_slaughterhouse, Some(id) if id != payload.slaughterhouse_id) {
+            return Err(Message::invalid(
+                "link.linked_id",
+                FieldErrorCode::InvalidValue,
+                format!(
+                    "The {} belongs to another slaughterhouse",
+                    link.linked_type.as_str()
+                ),
+            ));
+        }
+    }
+
+    let ncr_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let report = NonConformanceReport {
+        id: ncr_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        link: payload.link,
+        severity: payload.severity,
+        description: payload.description,
+        raised_by: payload.raised_by,
+        raised_at: time(),
+        status: NcrStatus::Open,
+        ..Default::default()
+    };
+
+    NON_CONFORMANCES.with(|reports| {
+        reports.borrow_mut().insert(ncr_id, report.clone());
+    });
+
+    Ok(report)
+}
+
+// Slaughterhouse of the record a report is linked to; suppliers serve every slaughterhouse
+fn linked_record_slaughterhouse(
+    linked_type: NcrLinkType,
+    linked_id: u64,
+) -> Result<Option<u64>, Message> {
+    let (entity, slaughterhouse_id) = match linked_type {
+        NcrLinkType::QualityInspection => (
+            "quality_inspection",
+            QUALITY_INSPECTIONS
+                .with(|inspections| inspections.borrow().get(&linked_id))
+                .and_then(|inspection| {
+                    ANIMALS.with(|animals| animals.borrow().get(&inspection.animal_id))
+                })
+                .map(|animal| Some(animal.slaughterhouse_id)),
+        ),
+        NcrLinkType::Shipment => (
+            "shipment",
+            SHIPMENTS
+                .with(|shipments| shipments.borrow().get(&linked_id))
+                .map(|shipment| Some(shipment.slaughterhouse_id)),
+        ),
+        NcrLinkType::Maintenance => (
+            "maintenance",
+            MAINTENANCE_RECORDS
+                .with(|records| records.borrow().get(&linked_id))
+                .map(|record| Some(record.slaughterhouse_id)),
+        ),
+        NcrLinkType::Supplier => (
+            "supplier",
+            SUPPLIERS
+                .with(|suppliers| suppliers.borrow().get(&linked_id))
+                .map(|_| None),
+        ),
+    };
+
+    slaughterhouse_id.ok_or_else(|| Message::not_found(entity, linked_id))
+}
+
+// Record NCR Investigation with the root cause and containment
+#[ic_cdk::update]
+fn record_ncr_investigation(
+    payload: NcrInvestigationPayload,
+) -> Result<NonConformanceReport, Message> {
+    Validator::new()
+        .text("root_cause", &payload.root_cause, MAX_NCR_TEXT_LENGTH)
+        .text(
+            "containment_action",
+            &payload.containment_action,
+            MAX_NCR_TEXT_LENGTH,
+        )
+        .finish()?;
+
+    let mut report = NON_CONFORMANCES
+        .with(|reports| reports.borrow().get(&payload.ncr_id))
+        .ok_or_else(|| Message::not_found("non_conformance", payload.ncr_id))?;
+
+    if report.status == NcrStatus::Closed {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "Non-conformance report is closed")
+                .on("non_conformance", report.id),
+        );
+    }
+
+    report.root_cause = payload.root_cause;
+    report.containment_action = payload.containment_action;
+    report.status = NcrStatus::Investigated;
+
+    NON_CONFORMANCES.with(|reports| {
+        reports.borrow_mut().insert(report.id, report.clone());
+    });
+
+    Ok(report)
+}
+
+// Add CAPA Action, owned by an active employee of the report's slaughterhouse
+#[ic_cdk::update]
+fn add_capa_action(payload: CapaActionPayload) -> Result<CapaAction, Message> {
+    Validator::new()
+        .text("description", &payload.description, MAX_NCR_TEXT_LENGTH)
+        .check(
+            payload.due_date != 0,
+            "due_date",
+            FieldErrorCode::Required,
+            "due_date is required",
+        )
+        .finish()?;
+
+    let report = NON_CONFORMANCES
+        .with(|reports| reports.borrow().get(&payload.ncr_id))
+        .ok_or_else(|| Message::not_found("non_conformance", payload.ncr_id))?;
+
+    match report.status {
+        NcrStatus::Open => {
+            return Err(Message::error(
+                ErrorCode::InvalidState,
+                "Record the root cause before adding actions",
+            )
+            .on("non_conformance", report.id))
+        }
+        NcrStatus::Closed => {
+            return Err(
+                Message::error(ErrorCode::InvalidState, "Non-conformance report is closed")
+                    .on("non_conformance", report.id),
+            )
+        }
+        NcrStatus::Investigated => {}
+    }
+
+    let owner = EMPLOYEES
+        .with(|employees| employees.borrow().get(&payload.owner_id))
+        .ok_or_else(|| Message::not_found("employee", payload.owner_id))?;
+    if owner.status != EmployeeStatus::Active || owner.slaughterhouse_id != report.slaughterhouse_id
+    {
+        return Err(Message::invalid(
+            "owner_id",
+            FieldErrorCode::InvalidValue,
+            "Action owner must be an active employee of the slaughterhouse",
+        ));
+    }
+
+    let action_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let action = CapaAction {
+        id: action_id,
+        ncr_id: report.id,
+        action_type: payload.action_type,
+        description: payload.description,
+        owner_id: owner.id,
+        due_date: payload.due_date,
+        created_at: time(),
+        status: CapaStatus::Open,
+        ..Default::default()
+    };
+
+    CAPA_ACTIONS.with(|actions| {
+        actions.borrow_mut().insert(action_id, action.clone());
+    });
+
+    Ok(action)
+}
+
+// Complete CAPA Action
+#[ic_cdk::update]
+fn complete_capa_action(action_id: u64, notes: String) -> Result<CapaAction, Message> {
+    Validator::new()
+        .max_length("notes", &notes, MAX_NCR_TEXT_LENGTH)
+        .finish()?;
+
+    let mut action = CAPA_ACTIONS
+        .with(|actions| actions.borrow().get(&action_id))
+        .ok_or_else(|| Message::not_found("capa_action", action_id))?;
+
+    if action.status != CapaStatus::Open {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "CAPA action is already completed")
+                .on("capa_action", action_id),
+        );
+    }
+
+    action.status = CapaStatus::Completed;
+    action.completed_at = time();
+    action.completion_notes = notes;
+
+    CAPA_ACTIONS.with(|actions| {
+        actions.borrow_mut().insert(action.id, action.clone());
+    });
+
+    Ok(action)
+}
+
+fn ncr_actions(ncr_id: u64) -> Vec<CapaAction> {
+    CAPA_ACTIONS.with(|actions| {
+        actions
+            .borrow()
+            .iter()
+            .filter(|(_, action)| action.ncr_id == ncr_id)
+            .map(|(_, action)| action)
+            .collect()
+    })
+}
+
+// Verify NCR Effectiveness; an effective verification closes the report, an
+// ineffective one sends it back for further corrective action
+#[ic_cdk::update]
+fn verify_ncr_effectiveness(
+    payload: NcrVerificationPayload,
+) -> Result<NonConformanceReport, Message> {
+    Validator::new()
+        .text("verified_by", &payload.verified_by, MAX_NAME_LENGTH)
+        .max_length("notes", &payload.notes, MAX_NCR_TEXT_LENGTH)
+        .finish()?;
+
+    let mut report = NON_CONFORMANCES
+        .with(|reports| reports.borrow().get(&payload.ncr_id))
+        .ok_or_else(|| Message::not_found("non_conformance", payload.ncr_id))?;
+
+    if report.status != NcrStatus::Investigated {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Cannot verify a report with status {}",
+                report.status.as_str()
+            ),
+        )
+        .on("non_conformance", report.id));
+    }
+
+    let actions = ncr_actions(report.id);
+    if actions
+        .iter()
+        .any(|action| action.status != CapaStatus::Completed)
+    {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            "All CAPA actions must be completed before verification",
+        )
+        .on("non_conformance", report.id));
+    }
+    // After a failed verification, new corrective action is needed before verifying again
+    let has_new_corrective_action = actions.iter().any(|action| {
+        action.action_type == CapaActionType::Corrective
+            && action.created_at > report.last_verified_at
+    });
+    if !has_new_corrective_action {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            "A completed corrective action is required before verification",
+        )
+        .on("non_conformance", report.id));
+    }
+
+    let now = time();
+    report.last_verified_at = now;
+    report.verified_by = payload.verified_by;
+    report.verification_notes = payload.notes;
+    report.effective = payload.effective;
+    if payload.effective {
+        report.status = NcrStatus::Closed;
+        report.closed_at = now;
+    }
+
+    NON_CONFORMANCES.with(|reports| {
+        reports.borrow_mut().insert(report.id, report.clone());
+    });
+
+    Ok(report)
+}
+
+#[ic_cdk::query]
+fn get_non_conformance(ncr_id: u64) -> Result<NcrDetails, Message> {
+    let report = NON_CONFORMANCES
+        .with(|reports| reports.borrow().get(&ncr_id))
+        .ok_or_else(|| Message::not_found("non_conformance", ncr_id))?;
+
+    Ok(NcrDetails {
+        actions: ncr_actions(report.id),
+        report,
+    })
+}
+
+#[ic_cdk::query]
+fn get_linked_non_conformances(
+    linked_type: NcrLinkType,
+    linked_id: u64,
+) -> Vec<NonConformanceReport> {
+    NON_CONFORMANCES.with(|reports| {
+        reports
+            .borrow()
+            .iter()
+            .filter(|(_, report)| match &report.link {
+                Some(link) => link.linked_type == linked_type && link.linked_id == linked_id,
+                None => false,
+            })
+            .map(|(_, report)| report)
+            .collect()
+    })
+}
+
+// Open CAPA actions past their due date
+#[ic_cdk::query]
+fn get_overdue_capa_actions(slaughterhouse_id: u64) -> Vec<CapaAction> {
+    let now = time();
+    CAPA_ACTIONS.with(|actions| {
+        actions
+            .borrow()
+            .iter()
+            .map(|(_, action)| action)
+            .filter(|action| action.status == CapaStatus::Open && action.due_date < now)
+            .filter(|action| {
+                NON_CONFORMANCES.with(|reports| {
+                    reports
+                        .borrow()
+                        .get(&action.ncr_id)
+                        .map(|report| report.slaughterhouse_id)
+                        == Some(slaughterhouse_id)
+                })
+            })
+            .collect()
+    })
+}
+
+// Link Employee Principal, restricted to canister controllers
+#[ic_cdk::update]
+fn link_employee_principal(
+    employee_id: u64,
+    principal: candid::Principal,
+) -> Result<Employee, Message> {
+    if !is_controller(&caller()) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a controller can link employee principals",
+        ));
+    }
+    if principal == candid::Principal::anonymous() {
+        return Err(Message::invalid(
+            "principal",
+            FieldErrorCode::InvalidValue,
+            "The anonymous principal cannot be linked",
+        ));
+    }
+
+    let employee = EMPLOYEES
+        .with(|employees| employees.borrow().get(&employee_id))
+        .ok_or_else(|| Message::not_found("employee", employee_id))?;
+
+    EMPLOYEE_PRINCIPALS.with(|principals| {
+        let mut principals = principals.borrow_mut();
+        // An employee acts through a single principal
+        let previous: Vec<PrincipalKey> = principals
+            .iter()
+            .filter(|(_, linked_id)| *linked_id == employee_id)
+            .map(|(key, _)| key)
+            .collect();
+        for key in previous {
+            principals.remove(&key);
+        }
+        principals.insert(PrincipalKey(principal.to_text()), employee_id);
+    });
+
+    Ok(employee)
+}
+
+// Set Employee Supervisor, restricted to canister controllers
+#[ic_cdk::update]
+fn set_employee_supervisor(employee_id: u64, supervisor: bool) -> Result<Employee, Message> {
+    if !is_controller(&caller()) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a controller can appoint supervisors",
+        ));
+    }
+
+    let mut employee = EMPLOYEES
+        .with(|employees| employees.borrow().get(&employee_id))
+        .ok_or_else(|| Message::not_found("employee", employee_id))?;
+    employee.supervisor = supervisor;
+
+    EMPLOYEES.with(|employees| {
+        employees.borrow_mut().insert(employee_id, employee.clone());
+    });
+
+    Ok(employee)
+}
+
+fn principal_employee(principal: &candid::Principal) -> Option<Employee> {
+    EMPLOYEE_PRINCIPALS
+        .with(|principals| principals.borrow().get(&PrincipalKey(principal.to_text())))
+        .and_then(|employee_id| EMPLOYEES.with(|employees| employees.borrow().get(&employee_id)))
+}
+
+// Employee linked to the calling principal
+fn caller_employee() -> Result<Employee, Message> {
+    principal_employee(&caller()).ok_or_else(|| {
+        Message::not_found_by("employee", "No employee is linked to the calling principal")
+    })
+}
+
+// Record Employee Certification
+#[ic_cdk::update]
+fn record_employee_certification(
+    payload: CertificationPayload,
+) -> Result<EmployeeCertification, Message> {
+    let certification_type = payload.certification_type.trim().to_lowercase();
+    Validator::new()
+        .reference("employee_id", "employee", payload.employee_id)
+        .text("certification_type", &certification_type, MAX_NAME_LENGTH)
+        .text(
+            "certificate_number",
+            &payload.certificate_number,
+            MAX_NAME_LENGTH,
+        )
+        .check(
+            payload.expires_at > payload.issued_at,
+            "expires_at",
+            FieldErrorCode::OutOfRange,
+            "Certification must expire after it is issued",
+        )
+        .finish()?;
+
+    let certification_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let certification = EmployeeCertification {
+        id: certification_id,
+        employee_id: payload.employee_id,
+        certification_type,
+        certificate_number: payload.certificate_number,
+        issued_at: payload.issued_at,
+        expires_at: payload.expires_at,
+    };
+
+    EMPLOYEE_CERTIFICATIONS.with(|certifications| {
+        certifications
+            .borrow_mut()
+            .insert(certification_id, certification.clone());
+    });
+
+    Ok(certification)
+}
+
+fn has_valid_certification(employee_id: u64, certification_type: &str, at: u64) -> bool {
+    EMPLOYEE_CERTIFICATIONS.with(|certifications| {
+        certifications.borrow().iter().any(|(_, certification)| {
+            certification.employee_id == employee_id
+                && certification.certification_type == certification_type
+                && certification.issued_at <= at
+                && at < certification.expires_at
+        })
+    })
+}
+
+#[ic_cdk::query]
+fn get_employee_certifications(employee_id: u64) -> Result<Vec<EmployeeCertification>, Message> {
+    let employee_exists = EMPLOYEES.with(|employees| employees.borrow().contains_key(&employee_id));
+    if !employee_exists {
+        return Err(Message::not_found("employee", employee_id));
+    }
+
+    Ok(EMPLOYEE_CERTIFICATIONS.with(|certifications| {
+        certifications
+            .borrow()
+            .iter()
+            .filter(|(_, certification)| certification.employee_id == employee_id)
+            .map(|(_, certification)| certification)
+            .collect()
+    }))
+}
+
+// Set Pay Rate, restricted to canister controllers
+#[ic_cdk::update]
+fn set_pay_rate(payload: PayRatePayload) -> Result<PayRate, Message> {
+    if !is_controller(&caller()) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a controller can set pay rates",
+        ));
+    }
+
+    Validator::new()
+        .reference("employee_id", "employee", payload.employee_id)
+        .positive("rate", payload.rate)
+        .finish()?;
+
+    let pay_rate = PayRate {
+        employee_id: payload.employee_id,
+        pay_type: payload.pay_type,
+        rate: payload.rate,
+        updated_at: time(),
+    };
+
+    PAY_RATES.with(|rates| {
+        rates
+            .borrow_mut()
+            .insert(payload.employee_id, pay_rate.clone());
+    });
+
+    Ok(pay_rate)
+}
+
+// Record Timesheet Entry for a day not yet covered by a payroll run
+#[ic_cdk::update]
+fn record_timesheet_entry(payload: TimesheetEntryPayload) -> Result<TimesheetEntry, Message> {
+    Validator::new()
+        .check(
+            payload.work_date != 0 && payload.work_date <= time(),
+            "work_date",
+            FieldErrorCode::OutOfRange,
+            "work_date must be set and not in the future",
+        )
+        .range("hours", payload.hours, 0.0, 24.0)
+        .check(
+            payload.hours > 0.0 || payload.heads > 0,
+            "hours",
+            FieldErrorCode::Required,
+            "An entry must record hours worked or heads processed",
+        )
+        .max_length("notes", &payload.notes, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let employee = EMPLOYEES
+        .with(|employees| employees.borrow().get(&payload.employee_id))
+        .ok_or_else(|| Message::not_found("employee", payload.employee_id))?;
+    if employee.status != EmployeeStatus::Active {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Cannot record time for a {} employee",
+                employee.status.as_str()
+            ),
+        )
+        .on("employee", employee.id));
+    }
+
+    if let Some(run) = payroll_run_covering(employee.slaughterhouse_id, payload.work_date) {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            "Payroll for this date has already been run",
+        )
+        .on("payroll_run", run.id));
+    }
+
+    // One entry per employee and day, so time is never paid twice
+    let work_day = payload.work_date / NANOS_PER_DAY;
+    let existing = TIMESHEET_ENTRIES.with(|entries| {
+        entries
+            .borrow()
+            .iter()
+            .map(|(_, entry)| entry)
+            .find(|entry| {
+                entry.employee_id == employee.id && entry.work_date / NANOS_PER_DAY == work_day
+            })
+    });
+    if let Some(existing) = existing {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!(
+                "Employee {} already has a timesheet entry for this day",
+                employee.id
+            ),
+        )
+        .on("timesheet_entry", existing.id));
+    }
+
+    let entry_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let entry = TimesheetEntry {
+        id: entry_id,
+        employee_id: employee.id,
+        slaughterhouse_id: employee.slaughterhouse_id,
+        work_date: payload.work_date,
+        hours: payload.hours,
+        heads: payload.heads,
+        notes: payload.notes,
+        recorded_at: time(),
+    };
+
+    TIMESHEET_ENTRIES.with(|entries| {
+        entries.borrow_mut().insert(entry_id, entry.clone());
+    });
+
+    Ok(entry)
+}
+
+// Share of an annual salary earned over the period, counted from the hire date when
+// the employee joined during it
+fn prorated_salary(annual_salary: f64, period_start: u64, period_end: u64, hire_date: u64) -> f64 {
+    let paid_from = period_start.max(hire_date);
+    let days = period_end.saturating_sub(paid_from) as f64 / NANOS_PER_DAY as f64;
+    annual_salary * days / 365.0
+}
+
+fn payroll_run_covering(slaughterhouse_id: u64, date: u64) -> Option<PayrollRun> {
+    PAYROLL_RUNS.with(|runs| {
+        runs.borrow().iter().map(|(_, run)| run).find(|run| {
+            run.slaughterhouse_id == slaughterhouse_id
+                && run.period_start <= date
+                && date < run.period_end
+        })
+    })
+}
+
+// Run Payroll, issuing a payslip to every employee with pay due in the period.
+// Hourly and piece-rate pay come from timesheets; salaries are prorated by day
+#[ic_cdk::update]
+fn run_payroll(payload: PayrollRunPayload) -> Result<PayrollRunDetails, Message> {
+    if !is_controller(&caller()) {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only a controller can run payroll",
+        ));
+    }
+
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .check(
+            payload.period_end > payload.period_start,
+            "period_end",
+            FieldErrorCode::OutOfRange,
+            "Payroll period is empty",
+        )
+        .finish()?;
+
+    let overlapping = PAYROLL_RUNS.with(|runs| {
+        runs.borrow().iter().map(|(_, run)| run).find(|run| {
+            run.slaughterhouse_id == payload.slaughterhouse_id
+                && run.period_start < payload.period_end
+                && payload.period_start < run.period_end
+        })
+    });
+    if let Some(run) = overlapping {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            "Payroll period overlaps an earlier run",
+        )
+        .on("payroll_run", run.id));
+    }
+
+    let employees: Vec<Employee> = EMPLOYEES.with(|employees| {
+        employees
+            .borrow()
+            .iter()
+            .map(|(_, employee)| employee)
+            .filter(|employee| employee.slaughterhouse_id == payload.slaughterhouse_id)
+            .collect()
+    });
+    let entries: Vec<TimesheetEntry> = TIMESHEET_ENTRIES.with(|entries| {
+        entries
+            .borrow()
+            .iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| {
+                entry.slaughterhouse_id == payload.slaughterhouse_id
+                    && entry.work_date >= payload.period_start
+                    && entry.work_date < payload.period_end
+            })
+            .collect()
+    });
+
+    // Work out every payslip before storing anything
+    let now = time();
+    let mut payslips = Vec::new();
+    let mut missing_rates = Vec::new();
+    for employee in employees {
+        let employee_entries: Vec<&TimesheetEntry> = entries
+            .iter()
+            .filter(|entry| entry.employee_id == employee.id)
+            .collect();
+        let hours: f64 = employee_entries.iter().map(|entry| entry.hours).sum();
+        let heads: u64 = employee_entries
+            .iter()
+            .map(|entry| entry.heads as u64)
+            .sum();
+
+        let pay_rate = PAY_RATES.with(|rates| rates.borrow().get(&employee.id));
+        let pay_rate = match pay_rate {
+            Some(pay_rate) => pay_rate,
+            None => {
+                if !employee_entries.is_empty() {
+                    missing_rates.push(employee.id.to_string());
+                }
+                continue;
+            }
+        };
+
+        let gross_pay = match pay_rate.pay_type {
+            PayType::Hourly => hours * pay_rate.rate,
+            PayType::PieceRate => heads as f64 * pay_rate.rate,
+            PayType::Salaried if employee.status == EmployeeStatus::Active => prorated_salary(
+                pay_rate.rate,
+                payload.period_start,
+                payload.period_end,
+                employee.hire_date,
+            ),
+            PayType::Salaried => 0.0,
+        };
+        if gross_pay <= 0.0 && employee_entries.is_empty() {
+            continue;
+        }
+
+        payslips.push(Payslip {
+            employee_id: employee.id,
+            slaughterhouse_id: payload.slaughterhouse_id,
+            period_start: payload.period_start,
+            period_end: payload.period_end,
+            pay_type: pay_rate.pay_type,
+            rate: pay_rate.rate,
+            hours,
+            heads,
+            gross_pay,
+            issued_at: now,
+            ..Default::default()
+        });
+    }
+
+    if !missing_rates.is_empty() {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!(
+                "Employees {} have timesheet entries but no pay rate",
+                missing_rates.join(", ")
+            ),
+        ));
+    }
+
+    let run_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    for payslip in payslips.iter_mut() {
+        payslip.id = ID_COUNTER.with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter
+                .borrow_mut()
+                .set(current_value + 1)
+                .expect("Counter increment failed");
+            current_value
+        });
+        payslip.payroll_run_id = run_id;
+        PAYSLIPS.with(|stored| {
+            stored.borrow_mut().insert(payslip.id, payslip.clone());
+        });
+    }
+
+    let run = PayrollRun {
+        id: run_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        period_start: payload.period_start,
+        period_end: payload.period_end,
+        employee_count: payslips.len() as u32,
+        total_gross_pay: payslips.iter().map(|payslip| payslip.gross_pay).sum(),
+        run_by: caller().to_text(),
+        run_at: now,
+    };
+
+    PAYROLL_RUNS.with(|runs| {
+        runs.borrow_mut().insert(run_id, run.clone());
+    });
+
+    Ok(PayrollRunDetails { run, payslips })
+}
+
+#[ic_cdk::query]
+fn get_payroll_run(run_id: u64) -> Result<PayrollRunDetails, Message> {
+    let run = PAYROLL_RUNS
+        .with(|runs| runs.borrow().get(&run_id))
+        .ok_or_else(|| Message::not_found("payroll_run", run_id))?;
+
+    let payslips = PAYSLIPS.with(|payslips| {
+        payslips
+            .borrow()
+            .iter()
+            .map(|(_, payslip)| payslip)
+            .filter(|payslip| payslip.payroll_run_id == run_id)
+            .collect()
+    });
+
+    Ok(PayrollRunDetails { run, payslips })
+}
+
+#[ic_cdk::query]
+fn get_employee_payslips(employee_id: u64) -> Result<Vec<Payslip>, Message> {
+    let employee_exists = EMPLOYEES.with(|employees| employees.borrow().contains_key(&employee_id));
+    if !employee_exists {
+        return Err(Message::not_found("employee", employee_id));
+    }
+
+    Ok(PAYSLIPS.with(|payslips| {
+        payslips
+            .borrow()
+            .iter()
+            .map(|(_, payslip)| payslip)
+            .filter(|payslip| payslip.employee_id == employee_id)
+            .collect()
+    }))
+}
+
+#[ic_cdk::query]
+fn get_timesheet_entries(employee_id: u64, start_date: u64, end_date: u64) -> Vec<TimesheetEntry> {
+    TIMESHEET_ENTRIES.with(|entries| {
+        entries
+            .borrow()
+            .iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| {
+                entry.employee_id == employee_id
+                    && entry.work_date >= start_date
+                    && entry.work_date <= end_date
+            })
+            .collect()
+    })
+}
+
+// Start Upload, creating a blob that receives its chunks in order
+#[ic_cdk::update]
+fn start_upload(payload: UploadSessionPayload) -> Result<Blob, Message> {
+    let mime_type = payload.mime_type.trim().to_lowercase();
+    let sha256 = payload.sha256.trim().to_lowercase();
+    Validator::new()
+        .text("file_name", &payload.file_name, MAX_FILE_NAME_LENGTH)
+        .check(
+            !payload
+                .file_name
+                .chars()
+                .any(|c| c.is_control() || c == '"' || c == '/' || c == '\\'),
+            "file_name",
+            FieldErrorCode::InvalidFormat,
+            "File names cannot contain control characters, quotes or slashes",
+        )
+        .check(
+            payload.size > 0 && payload.size <= MAX_BLOB_SIZE,
+            "size",
+            FieldErrorCode::OutOfRange,
+            format!("Files must hold 1-{} bytes", MAX_BLOB_SIZE),
+        )
+        .check(
+            ALLOWED_MIME_TYPES.contains(&mime_type.as_str()),
+            "mime_type",
+            FieldErrorCode::InvalidValue,
+            format!(
+                "Unsupported MIME type {}, expected one of {}",
+                payload.mime_type,
+                ALLOWED_MIME_TYPES.join(", ")
+            ),
+        )
+        .check(
+            sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()),
+            "sha256",
+            FieldErrorCode::InvalidFormat,
+            "SHA-256 digest must be 64 hex characters",
+        )
+        .finish()?;
+
+    let blob_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let blob = Blob {
+        id: blob_id,
+        file_name: payload.file_name,
+        mime_type,
+        size: payload.size,
+        sha256,
+        status: BlobStatus::Uploading,
+        uploaded_by: caller().to_text(),
+        created_at: time(),
+        ..Default::default()
+    };
+
+    BLOBS.with(|blobs| {
+        blobs.borrow_mut().insert(blob_id, blob.clone());
+    });
+
+    Ok(blob)
+}
+
+// Blob still being uploaded by the caller
+fn uploading_blob(blob_id: u64) -> Result<Blob, Message> {
+    let blob = BLOBS
+        .with(|blobs| blobs.borrow().get(&blob_id))
+        .ok_or_else(|| Message::not_found("blob", blob_id))?;
+
+    if blob.uploaded_by != caller().to_text() {
+        return Err(Message::error(
+            ErrorCode::Unauthorized,
+            "Only the uploader can continue an upload",
+        )
+        .on("blob", blob_id));
+    }
+    if blob.status != BlobStatus::Uploading {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!("Upload is {}", blob.status.as_str()),
+        )
+        .on("blob", blob_id));
+    }
+
+    Ok(blob)
+}
+
+// Upload Chunk; chunks are appended in index order
+#[ic_cdk::update]
+fn upload_chunk(blob_id: u64, index: u64, data: Vec<u8>) -> Result<Blob, Message> {
+    if data.is_empty() || data.len() > MAX_CHUNK_SIZE {
+        return Err(Message::invalid(
+            "data",
+            FieldErrorCode::OutOfRange,
+            format!("Chunks must hold 1-{} bytes", MAX_CHUNK_SIZE),
+        ));
+    }
+
+    let mut blob = uploading_blob(blob_id)?;
+    if index != blob.chunk_count {
+        return Err(Message::invalid(
+            "index",
+            FieldErrorCode::InvalidValue,
+            format!("Expected chunk {}", blob.chunk_count),
+        ));
+    }
+    if blob.bytes_received + data.len() as u64 > blob.size {
+        return Err(Message::invalid(
+            "data",
+            FieldErrorCode::OutOfRange,
+            "Chunk exceeds the declared file size",
+        ));
+    }
+
+    blob.bytes_received += data.len() as u64;
+    blob.chunk_count += 1;
+
+    BLOB_CHUNKS.with(|chunks| {
+        chunks
+            .borrow_mut()
+            .insert((blob_id, index), BlobChunk(data));
+    });
+    BLOBS.with(|blobs| {
+        blobs.borrow_mut().insert(blob_id, blob.clone());
+    });
+
+    Ok(blob)
+}
+
+fn remove_blob_chunks(blob: &Blob) {
+    BLOB_CHUNKS.with(|chunks| {
+        let mut chunks = chunks.borrow_mut();
+        for index in 0..blob.chunk_count {
+            chunks.remove(&(blob.id, index));
+        }
+    });
+}
+
+// Finish Upload, verifying the size and SHA-256 digest; a mismatch rejects the blob
+#[ic_cdk::update]
+fn finish_upload(blob_id: u64) -> Result<Blob, Message> {
+    let mut blob = uploading_blob(blob_id)?;
+    if blob.bytes_received != blob.size {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            format!("Received {} of {} bytes", blob.bytes_received, blob.size),
+        )
+        .on("blob", blob_id));
+    }
+
+    let mut hasher = Sha256::new();
+    BLOB_CHUNKS.with(|chunks| {
+        let chunks = chunks.borrow();
+        for index in 0..blob.chunk_count {
+            if let Some(chunk) = chunks.get(&(blob_id, index)) {
+                hasher.update(&chunk.0);
+            }
+        }
+    });
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let verified = digest == blob.sha256;
+    if verified {
+        blob.status = BlobStatus::Complete;
+        blob.completed_at = time();
+    } else {
+        remove_blob_chunks(&blob);
+        blob.status = BlobStatus::Rejected;
+    }
+    BLOBS.with(|blobs| {
+        blobs.borrow_mut().insert(blob_id, blob.clone());
+    });
+
+    if !verified {
+        return Err(Message::invalid(
+            "sha256",
+            FieldErrorCode::InvalidValue,
+            format!(
+                "SHA-256 mismatch: expected {}, received {}",
+                blob.sha256, digest
+            ),
+        )
+        .on("blob", blob_id));
+    }
+    Ok(blob)
+}
+
+// Cancel Upload, discarding the chunks received so far
+#[ic_cdk::update]
+fn cancel_upload(blob_id: u64) -> Result<Blob, Message> {
+    let mut blob = uploading_blob(blob_id)?;
+    remove_blob_chunks(&blob);
+    blob.status = BlobStatus::Rejected;
+
+    BLOBS.with(|blobs| {
+        blobs.borrow_mut().insert(blob_id, blob.clone());
+    });
+
+    Ok(blob)
+}
+
+fn attachment_record_exists(record_type: &str, record_id: u64) -> Result<bool, Message> {
+    match record_type {
+        "quality_inspection" => {
+            Ok(QUALITY_INSPECTIONS
+                .with(|inspections| inspections.borrow().contains_key(&record_id)))
+        }
+        "maintenance" => {
+            Ok(MAINTENANCE_RECORDS.with(|records| records.borrow().contains_key(&record_id)))
+        }
+        "shipment" => Ok(SHIPMENTS.with(|shipments| shipments.borrow().contains_key(&record_id))),
+        "waste" => Ok(WASTE_RECORDS.with(|records| records.borrow().contains_key(&record_id))),
+        "waste_manifest" => {
+            Ok(WASTE_MANIFESTS.with(|manifests| manifests.borrow().contains_key(&record_id)))
+        }
+        _ => Err(Message::invalid(
+            "record_type",
+            FieldErrorCode::InvalidValue,
+            format!(
+                "Unknown record type {}, expected one of {}",
+                record_type,
+                ATTACHMENT_RECORD_TYPES.join(", ")
+            ),
+        )),
+    }
+}
+
+// Attach Blob to a record
+#[ic_cdk::update]
+fn attach_blob(payload: AttachmentPayload) -> Result<Attachment, Message> {
+    Validator::new()
+        .max_length("description", &payload.description, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    if !attachment_record_exists(&payload.record_type, payload.record_id)? {
+        return Err(Message::not_found(&payload.record_type, payload.record_id));
+    }
+
+    let blob = BLOBS
+        .with(|blobs| blobs.borrow().get(&payload.blob_id))
+        .ok_or_else(|| Message::not_found("blob", payload.blob_id))?;
+    if blob.status != BlobStatus::Complete {
+        return Err(Message::error(
+            ErrorCode::InvalidState,
+            "Only completed uploads can be attached",
+        )
+        .on("blob", blob.id));
+    }
+
+    let attachment_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let attachment = Attachment {
+        id: attachment_id,
+        blob_id: blob.id,
+        record_type: payload.record_type,
+        record_id: payload.record_id,
+        description: payload.description,
+        attached_by: caller().to_text(),
+        attached_at: time(),
+    };
+
+    ATTACHMENTS.with(|attachments| {
+        attachments
+            .borrow_mut()
+            .insert(attachment_id, attachment.clone());
+    });
+
+    Ok(attachment)
+}
+
+#[ic_cdk::query]
+fn get_attachments(record_type: String, record_id: u64) -> Vec<Attachment> {
+    ATTACHMENTS.with(|attachments| {
+        attachments
+            .borrow()
+            .iter()
+            .filter(|(_, attachment)| {
+                attachment.record_type == record_type && attachment.record_id == record_id
+            })
+            .map(|(_, attachment)| attachment)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_blob(blob_id: u64) -> Result<Blob, Message> {
+    BLOBS
+        .with(|blobs| blobs.borrow().get(&blob_id))
+        .ok_or_else(|| Message::not_found("blob", blob_id))
+}
+
+#[ic_cdk::query]
+fn get_blob_chunk(blob_id: u64, index: u64) -> Result<Vec<u8>, Message> {
+    let blob = get_blob(blob_id)?;
+    if blob.status != BlobStatus::Complete {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "Blob upload is not complete")
+                .on("blob", blob_id),
+        );
+    }
+
+    BLOB_CHUNKS
+        .with(|chunks| chunks.borrow().get(&(blob_id, index)))
+        .map(|chunk| chunk.0)
+        .ok_or_else(|| {
+            Message::not_found_by(
+                "blob_chunk",
+                format!("Blob {} has no chunk {}", blob_id, index),
+            )
+        })
+}
+
+// Token for the chunk after the given one, if the blob has more
+fn next_chunk_token(blob: &Blob, index: u64) -> Option<StreamingCallbackToken> {
+    if index + 1 < blob.chunk_count {
+        Some(StreamingCallbackToken {
+            blob_id: blob.id,
+            index: index + 1,
+        })
+    } else {
+        None
+    }
+}
+
+// Serves completed blobs at /attachments/<blob id>, streaming the remaining chunks
+#[ic_cdk::query]
+fn http_request(request: HttpRequest) -> HttpResponse {
+    let not_found = |message: &str| HttpResponse {
+        status_code: 404,
+        headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+        body: message.as_bytes().to_vec(),
+        streaming_strategy: None,
+    };
+    if request.method != "GET" {
+        return HttpResponse {
+            status_code: 405,
+            ..not_found("Method not allowed")
+        };
+    }
+
+    let path = request.url.split('?').next().unwrap_or_default();
+    let blob = match path
+        .strip_prefix("/attachments/")
+        .and_then(|id| id.parse::<u64>().ok())
+        .and_then(|blob_id| BLOBS.with(|blobs| blobs.borrow().get(&blob_id)))
+    {
+        Some(blob) if blob.status == BlobStatus::Complete => blob,
+        _ => return not_found("Attachment not found"),
+    };
+
+    let body = BLOB_CHUNKS
+        .with(|chunks| chunks.borrow().get(&(blob.id, 0)))
+        .map(|chunk| chunk.0)
+        .unwrap_or_default();
+    let streaming_strategy = next_chunk_token(&blob, 0).map(|token| StreamingStrategy::Callback {
+        callback: candid::Func {
+            principal: ic_cdk::id(),
+            method: "http_request_streaming_callback".to_string(),
+        },
+        token,
+    });
+
+    HttpResponse {
+        status_code: 200,
+        headers: vec![
+            ("Content-Type".to_string(), blob.mime_type.clone()),
+            ("Content-Length".to_string(), blob.size.to_string()),
+            (
+                "Content-Disposition".to_string(),
+                format!("inline; filename=\"{}\"", blob.file_name),
+            ),
+        ],
+        body,
+        streaming_strategy,
+    }
+}
 
-    static SLAUGHTERHOUSES: RefCell<StableBTreeMap<u64, Slaughterhouse, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
-        ));
+#[ic_cdk::query]
+fn http_request_streaming_callback(token: StreamingCallbackToken) -> StreamingCallbackHttpResponse {
+    let blob = match BLOBS.with(|blobs| blobs.borrow().get(&token.blob_id)) {
+        Some(blob) if blob.status == BlobStatus::Complete => blob,
+        _ => ic_cdk::trap("Attachment not found"),
+    };
 
-    static ANIMALS: RefCell<StableBTreeMap<u64, Animal, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
-        ));
+    StreamingCallbackHttpResponse {
+        body: BLOB_CHUNKS
+            .with(|chunks| chunks.borrow().get(&(blob.id, token.index)))
+            .map(|chunk| chunk.0)
+            .unwrap_or_default(),
+        token: next_chunk_token(&blob, token.index),
+    }
+}
 
-    static MEAT_PRODUCTS: RefCell<StableBTreeMap<u64, MeatProduct, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
-        ));
+// Register Instrument; it is out of calibration until its first calibration passes
+#[ic_cdk::update]
+fn register_instrument(payload: InstrumentPayload) -> Result<Instrument, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("serial_number", &payload.serial_number, MAX_NAME_LENGTH)
+        .max_length("model", &payload.model, MAX_NAME_LENGTH)
+        .check(
+            (1..=MAX_CALIBRATION_INTERVAL_DAYS).contains(&payload.calibration_interval_days),
+            "calibration_interval_days",
+            FieldErrorCode::OutOfRange,
+            format!(
+                "calibration_interval_days must be between 1 and {}",
+                MAX_CALIBRATION_INTERVAL_DAYS
+            ),
+        )
+        .finish()?;
+
+    let instrument_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
 
-    static EXPENSES: RefCell<StableBTreeMap<u64, Expense, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
-        ));
+    let instrument = Instrument {
+        id: instrument_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        instrument_type: payload.instrument_type,
+        serial_number: payload.serial_number,
+        model: payload.model,
+        calibration_interval_days: payload.calibration_interval_days,
+        status: InstrumentStatus::Active,
+        registered_at: time(),
+        ..Default::default()
+    };
 
-    static QUALITY_INSPECTIONS: RefCell<StableBTreeMap<u64, QualityInspection, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
-        ));
+    INSTRUMENTS.with(|instruments| {
+        instruments
+            .borrow_mut()
+            .insert(instrument_id, instrument.clone());
+    });
 
-    static EMPLOYEES: RefCell<StableBTreeMap<u64, Employee, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
-        ));
+    Ok(instrument)
+}
 
-    static MAINTENANCE_RECORDS: RefCell<StableBTreeMap<u64, MaintenanceRecord, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
-        ));
+// Record Calibration; a reading within tolerance renews the calibration, otherwise
+// the instrument is out of calibration from now on
+#[ic_cdk::update]
+fn record_calibration(payload: CalibrationPayload) -> Result<CalibrationRecord, Message> {
+    Validator::new()
+        .text("performed_by", &payload.performed_by, MAX_NAME_LENGTH)
+        .text(
+            "certificate_number",
+            &payload.certificate_number,
+            MAX_NAME_LENGTH,
+        )
+        .range(
+            "reference_value",
+            payload.reference_value,
+            f64::MIN,
+            f64::MAX,
+        )
+        .range("measured_value", payload.measured_value, f64::MIN, f64::MAX)
+        .non_negative("tolerance", payload.tolerance)
+        .max_length("notes", &payload.notes, MAX_NOTES_LENGTH)
+        .finish()?;
+
+    let mut instrument = INSTRUMENTS
+        .with(|instruments| instruments.borrow().get(&payload.instrument_id))
+        .ok_or_else(|| Message::not_found("instrument", payload.instrument_id))?;
+    if instrument.status != InstrumentStatus::Active {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "Instrument is retired")
+                .on("instrument", instrument.id),
+        );
+    }
 
-    static SUPPLIERS: RefCell<StableBTreeMap<u64, Supplier, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17)))
-        ));
+    let calibration_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+    let now = time();
+
+    let calibration = CalibrationRecord {
+        id: calibration_id,
+        instrument_id: instrument.id,
+        calibrated_at: now,
+        performed_by: payload.performed_by,
+        certificate_number: payload.certificate_number,
+        reference_value: payload.reference_value,
+        measured_value: payload.measured_value,
+        tolerance: payload.tolerance,
+        passed: (payload.measured_value - payload.reference_value).abs() <= payload.tolerance,
+        notes: payload.notes,
+    };
 
-    static SHIPMENTS: RefCell<StableBTreeMap<u64, Shipment, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
-        ));
+    if calibration.passed {
+        instrument.last_calibrated_at = now;
+        instrument.calibration_due_at = now.saturating_add(
+            instrument
+                .calibration_interval_days
+                .saturating_mul(NANOS_PER_DAY),
+        );
+    } else {
+        instrument.calibration_due_at = now;
+    }
 
-    static WASTE_RECORDS: RefCell<StableBTreeMap<u64, WasteRecord, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
-        ));
+    CALIBRATION_RECORDS.with(|records| {
+        records
+            .borrow_mut()
+            .insert(calibration_id, calibration.clone());
+    });
+    INSTRUMENTS.with(|instruments| {
+        instruments.borrow_mut().insert(instrument.id, instrument);
+    });
 
-    static FINANCIAL_METRICS: RefCell<StableBTreeMap<u64, FinancialMetrics, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))
-        ));
+    Ok(calibration)
+}
+
+// Retire Instrument
+#[ic_cdk::update]
+fn retire_instrument(instrument_id: u64) -> Result<Instrument, Message> {
+    let mut instrument = INSTRUMENTS
+        .with(|instruments| instruments.borrow().get(&instrument_id))
+        .ok_or_else(|| Message::not_found("instrument", instrument_id))?;
+
+    instrument.status = InstrumentStatus::Retired;
+
+    INSTRUMENTS.with(|instruments| {
+        instruments
+            .borrow_mut()
+            .insert(instrument_id, instrument.clone());
+    });
 
+    Ok(instrument)
+}
 
+fn is_in_calibration(instrument: &Instrument, at: u64) -> bool {
+    instrument.status == InstrumentStatus::Active && at < instrument.calibration_due_at
 }
 
-// Functions
+// Checks the instrument used for a reading before the record is stored. Unknown
+// instruments, or ones of the wrong type or slaughterhouse, are always rejected;
+// missing or uncalibrated ones reject temperature and pH readings and flag the rest
+fn check_reading(
+    instrument_id: Option<u64>,
+    reading_kind: &str,
+    slaughterhouse_id: u64,
+) -> Result<InstrumentReading, Message> {
+    let flag_reason = match instrument_id {
+        Some(instrument_id) => {
+            let instrument = INSTRUMENTS
+                .with(|instruments| instruments.borrow().get(&instrument_id))
+                .ok_or_else(|| Message::not_found("instrument", instrument_id))?;
+
+            let expected_type = READING_INSTRUMENT_TYPES
+                .iter()
+                .find(|(kind, _)| *kind == reading_kind)
+                .map(|(_, instrument_type)| *instrument_type);
+            if expected_type != Some(instrument.instrument_type) {
+                return Err(Message::invalid(
+                    "instrument_id",
+                    FieldErrorCode::InvalidValue,
+                    format!(
+                        "Instrument {} is a {}, {} readings need a {}",
+                        instrument.id,
+                        instrument.instrument_type.as_str(),
+                        reading_kind,
+                        expected_type.map_or("different instrument", StatusLabel::as_str)
+                    ),
+                )
+                .on("instrument", instrument.id));
+            }
+            if instrument.slaughterhouse_id != slaughterhouse_id {
+                return Err(Message::invalid(
+                    "instrument_id",
+                    FieldErrorCode::InvalidValue,
+                    format!(
+                        "Instrument {} belongs to another slaughterhouse",
+                        instrument.id
+                    ),
+                )
+                .on("instrument", instrument.id));
+            }
+
+            if is_in_calibration(&instrument, time()) {
+                String::new()
+            } else {
+                format!("Instrument {} is out of calibration", instrument.id)
+            }
+        }
+        None => "No instrument recorded".to_string(),
+    };
 
-// Create Slaughterhouse
+    if !flag_reason.is_empty() && REJECT_UNCALIBRATED_READINGS.contains(&reading_kind) {
+        let rejection = Message::error(
+            ErrorCode::Blocked,
+            format!("{} reading rejected: {}", reading_kind, flag_reason),
+        );
+        return Err(match instrument_id {
+            Some(instrument_id) => rejection.on("instrument", instrument_id),
+            None => rejection,
+        });
+    }
+
+    Ok(InstrumentReading {
+        instrument_id,
+        slaughterhouse_id,
+        reading_kind: reading_kind.to_string(),
+        flagged: !flag_reason.is_empty(),
+        flag_reason,
+        ..Default::default()
+    })
+}
+
+// Stores a reading checked by check_reading against the record it was taken for
+fn record_reading(mut reading: InstrumentReading, record_type: &str, record_id: u64, value: f64) {
+    reading.id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+    reading.record_type = record_type.to_string();
+    reading.record_id = record_id;
+    reading.value = value;
+    reading.recorded_at = time();
+
+    INSTRUMENT_READINGS.with(|readings| {
+        readings.borrow_mut().insert(reading.id, reading);
+    });
+}
+
+#[ic_cdk::query]
+fn get_instrument(instrument_id: u64) -> Result<InstrumentDetails, Message> {
+    let instrument = INSTRUMENTS
+        .with(|instruments| instruments.borrow().get(&instrument_id))
+        .ok_or_else(|| Message::not_found("instrument", instrument_id))?;
+
+    let calibrations = CALIBRATION_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| record.instrument_id == instrument_id)
+            .map(|(_, record)| record)
+            .collect()
+    });
+
+    Ok(InstrumentDetails {
+        in_calibration: is_in_calibration(&instrument, time()),
+        instrument,
+        calibrations,
+    })
+}
+
+// Active instruments whose calibration is overdue or falls due within the given days
+#[ic_cdk::query]
+fn get_instruments_due(slaughterhouse_id: u64, within_days: u64) -> Vec<Instrument> {
+    let horizon = time().saturating_add(within_days.saturating_mul(NANOS_PER_DAY));
+
+    INSTRUMENTS.with(|instruments| {
+        instruments
+            .borrow()
+            .iter()
+            .map(|(_, instrument)| instrument)
+            .filter(|instrument| {
+                instrument.slaughterhouse_id == slaughterhouse_id
+                    && instrument.status == InstrumentStatus::Active
+                    && instrument.calibration_due_at <= horizon
+            })
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_record_readings(record_type: String, record_id: u64) -> Vec<InstrumentReading> {
+    INSTRUMENT_READINGS.with(|readings| {
+        readings
+            .borrow()
+            .iter()
+            .filter(|(_, reading)| {
+                reading.record_type == record_type && reading.record_id == record_id
+            })
+            .map(|(_, reading)| reading)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_flagged_readings(slaughterhouse_id: u64) -> Vec<InstrumentReading> {
+    INSTRUMENT_READINGS.with(|readings| {
+        readings
+            .borrow()
+            .iter()
+            .filter(|(_, reading)| {
+                reading.slaughterhouse_id == slaughterhouse_id && reading.flagged
+            })
+            .map(|(_, reading)| reading)
+            .collect()
+    })
+}
+
+// Register Waste Contractor
 #[ic_cdk::update]
-fn create_slaughterhouse(payload: CreateSlaughterhousePayload) -> Result<Slaughterhouse, Message> {
-    if payload.name.is_empty() || payload.contact.is_empty() || payload.email.is_empty() {
-        return Err(Message::InvalidPayload(
-            "Missing required fields".to_string(),
-        ));
+fn register_waste_contractor(payload: WasteContractorPayload) -> Result<WasteContractor, Message> {
+    let licence_number = payload.licence_number.trim().to_uppercase();
+    let mut validator = Validator::new();
+    validator
+        .text("name", &payload.name, MAX_NAME_LENGTH)
+        .text("licence_number", &licence_number, MAX_NAME_LENGTH)
+        .check(
+            payload.licence_expires_at > time(),
+            "licence_expires_at",
+            FieldErrorCode::OutOfRange,
+            "Licence has already expired",
+        )
+        .check(
+            !payload.permitted_categories.is_empty(),
+            "permitted_categories",
+            FieldErrorCode::Required,
+            "At least one by-product category must be permitted",
+        )
+        .text("contact", &payload.contact, MAX_NAME_LENGTH);
+    for (index, category) in payload.permitted_categories.iter().enumerate() {
+        validator.check(
+            !payload.permitted_categories[..index].contains(category),
+            &format!("permitted_categories[{}]", index),
+            FieldErrorCode::Duplicate,
+            format!("{} is listed more than once", category.label()),
+        );
     }
+    validator.finish()?;
 
-    let slaughterhouse_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+    let existing = WASTE_CONTRACTORS.with(|contractors| {
+        contractors
+            .borrow()
+            .iter()
+            .map(|(_, contractor)| contractor)
+            .find(|contractor| contractor.licence_number == licence_number)
+    });
+    if let Some(existing) = existing {
+        return Err(Message::error(
+            ErrorCode::AlreadyExists,
+            format!("Licence {} is already registered", licence_number),
+        )
+        .on("waste_contractor", existing.id));
+    }
+
+    let contractor_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let contractor = WasteContractor {
+        id: contractor_id,
+        name: payload.name,
+        licence_number,
+        licence_expires_at: payload.licence_expires_at,
+        permitted_categories: payload.permitted_categories,
+        contact: payload.contact,
+        registered_at: time(),
+    };
+
+    WASTE_CONTRACTORS.with(|contractors| {
+        contractors
+            .borrow_mut()
+            .insert(contractor_id, contractor.clone());
+    });
+
+    Ok(contractor)
+}
+
+// Renew Contractor Licence
+#[ic_cdk::update]
+fn renew_contractor_licence(
+    contractor_id: u64,
+    licence_expires_at: u64,
+) -> Result<WasteContractor, Message> {
+    let mut contractor = WASTE_CONTRACTORS
+        .with(|contractors| contractors.borrow().get(&contractor_id))
+        .ok_or_else(|| Message::not_found("waste_contractor", contractor_id))?;
+
+    Validator::new()
+        .check(
+            licence_expires_at > contractor.licence_expires_at.max(time()),
+            "licence_expires_at",
+            FieldErrorCode::OutOfRange,
+            "Renewed licence must expire after the current one",
+        )
+        .finish()?;
+
+    contractor.licence_expires_at = licence_expires_at;
+
+    WASTE_CONTRACTORS.with(|contractors| {
+        contractors
+            .borrow_mut()
+            .insert(contractor_id, contractor.clone());
+    });
+
+    Ok(contractor)
+}
+
+#[ic_cdk::query]
+fn get_waste_contractors() -> Vec<WasteContractor> {
+    WASTE_CONTRACTORS.with(|contractors| {
+        contractors
+            .borrow()
+            .iter()
+            .map(|(_, contractor)| contractor)
+            .collect()
+    })
+}
+
+// Create Waste Manifest for records collected by a licensed contractor
+#[ic_cdk::update]
+fn create_waste_manifest(payload: WasteManifestPayload) -> Result<WasteManifest, Message> {
+    let mut validator = Validator::new();
+    validator
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .reference("contractor_id", "waste_contractor", payload.contractor_id)
+        .check(
+            !payload.waste_record_ids.is_empty(),
+            "waste_record_ids",
+            FieldErrorCode::Required,
+            "A manifest must carry at least one waste record",
+        )
+        .max_count(
+            "waste_record_ids",
+            payload.waste_record_ids.len(),
+            MAX_MANIFEST_RECORDS,
+        )
+        .text("vehicle_plate", &payload.vehicle_plate, MAX_NAME_LENGTH)
+        .text(
+            "carrier_signed_by",
+            &payload.carrier_signed_by,
+            MAX_NAME_LENGTH,
+        )
+        .text(
+            "receiving_facility",
+            &payload.receiving_facility,
+            MAX_NAME_LENGTH,
+        )
+        .positive("gross_weight", payload.gross_weight)
+        .non_negative("tare_weight", payload.tare_weight)
+        .check(
+            payload.gross_weight > payload.tare_weight,
+            "tare_weight",
+            FieldErrorCode::OutOfRange,
+            "tare_weight must be below gross_weight",
+        );
+    for (index, record_id) in payload.waste_record_ids.iter().enumerate() {
+        validator.check(
+            !payload.waste_record_ids[..index].contains(record_id),
+            &format!("waste_record_ids[{}]", index),
+            FieldErrorCode::Duplicate,
+            format!("Waste record {} is listed more than once", record_id),
+        );
+    }
+    validator.finish()?;
+
+    let contractor = WASTE_CONTRACTORS
+        .with(|contractors| contractors.borrow().get(&payload.contractor_id))
+        .ok_or_else(|| Message::not_found("waste_contractor", payload.contractor_id))?;
+    let now = time();
+    if contractor.licence_expires_at <= now {
+        return Err(Message::error(
+            ErrorCode::Blocked,
+            format!("Licence {} has expired", contractor.licence_number),
+        )
+        .on("waste_contractor", contractor.id));
+    }
+
+    let mut declared_weight = 0.0;
+    for record_id in &payload.waste_record_ids {
+        let record = WASTE_RECORDS
+            .with(|records| records.borrow().get(record_id))
+            .ok_or_else(|| Message::not_found("waste_record", *record_id))?;
+        if record.slaughterhouse_id != payload.slaughterhouse_id {
+            return Err(Message::invalid(
+                "waste_record_ids",
+                FieldErrorCode::InvalidValue,
+                format!(
+                    "Waste record {} belongs to a different slaughterhouse",
+                    record_id
+                ),
+            ));
+        }
+        match record.category {
+            Some(category) if contractor.permitted_categories.contains(&category) => {}
+            Some(category) => {
+                return Err(Message::error(
+                    ErrorCode::Blocked,
+                    format!(
+                        "Contractor is not licensed to carry {} material",
+                        category.label()
+                    ),
+                )
+                .on("waste_contractor", contractor.id))
+            }
+            None => {
+                return Err(Message::error(
+                    ErrorCode::InvalidState,
+                    "Waste record has no by-product category",
+                )
+                .on("waste_record", record.id))
+            }
+        }
+        if let Some(manifest) = manifest_for_waste_record(record.id) {
+            return Err(Message::error(
+                ErrorCode::AlreadyExists,
+                format!(
+                    "Waste record {} is already on manifest {}",
+                    record.id, manifest.id
+                ),
+            )
+            .on("waste_manifest", manifest.id));
+        }
+        declared_weight += record.quantity;
+    }
+
+    let manifest_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let manifest = WasteManifest {
+        id: manifest_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        contractor_id: contractor.id,
+        waste_record_ids: payload.waste_record_ids,
+        vehicle_plate: payload.vehicle_plate,
+        carrier_signed_by: payload.carrier_signed_by,
+        receiving_facility: payload.receiving_facility,
+        declared_weight,
+        gross_weight: payload.gross_weight,
+        tare_weight: payload.tare_weight,
+        net_weight: payload.gross_weight - payload.tare_weight,
+        dispatched_at: now,
+        received_weight: 0.0,
+        confirmed_by: String::new(),
+        confirmed_at: 0,
+    };
+
+    WASTE_MANIFESTS.with(|manifests| {
+        manifests.borrow_mut().insert(manifest_id, manifest.clone());
+    });
+
+    Ok(manifest)
+}
+
+// Confirm Waste Manifest, called once the receiving facility has weighed the load
+#[ic_cdk::update]
+fn confirm_waste_manifest(payload: ManifestConfirmationPayload) -> Result<WasteManifest, Message> {
+    Validator::new()
+        .positive("received_weight", payload.received_weight)
+        .text("confirmed_by", &payload.confirmed_by, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let mut manifest = WASTE_MANIFESTS
+        .with(|manifests| manifests.borrow().get(&payload.manifest_id))
+        .ok_or_else(|| Message::not_found("waste_manifest", payload.manifest_id))?;
+
+    if manifest.confirmed_at != 0 {
+        return Err(
+            Message::error(ErrorCode::InvalidState, "Manifest is already confirmed")
+                .on("waste_manifest", manifest.id),
+        );
+    }
+
+    manifest.received_weight = payload.received_weight;
+    manifest.confirmed_by = payload.confirmed_by;
+    manifest.confirmed_at = time();
+
+    WASTE_MANIFESTS.with(|manifests| {
+        manifests.borrow_mut().insert(manifest.id, manifest.clone());
+    });
+
+    Ok(manifest)
+}
+
+fn manifest_for_waste_record(record_id: u64) -> Option<WasteManifest> {
+    WASTE_MANIFESTS.with(|manifests| {
+        manifests
+            .borrow()
+            .iter()
+            .map(|(_, manifest)| manifest)
+            .find(|manifest| manifest.waste_record_ids.contains(&record_id))
+    })
+}
+
+#[ic_cdk::query]
+fn get_waste_manifest(manifest_id: u64) -> Result<WasteManifest, Message> {
+    WASTE_MANIFESTS
+        .with(|manifests| manifests.borrow().get(&manifest_id))
+        .ok_or_else(|| Message::not_found("waste_manifest", manifest_id))
+}
+
+// Manifests dispatched more than the given days ago that the receiving facility has not confirmed
+#[ic_cdk::query]
+fn get_unconfirmed_manifests(slaughterhouse_id: u64, older_than_days: u64) -> Vec<WasteManifest> {
+    let cutoff = time().saturating_sub(older_than_days.saturating_mul(NANOS_PER_DAY));
+    WASTE_MANIFESTS.with(|manifests| {
+        manifests
+            .borrow()
+            .iter()
+            .map(|(_, manifest)| manifest)
+            .filter(|manifest| {
+                manifest.slaughterhouse_id == slaughterhouse_id
+                    && manifest.confirmed_at == 0
+                    && manifest.dispatched_at <= cutoff
+            })
+            .collect()
+    })
+}
+
+// Record Meter Reading
+#[ic_cdk::update]
+fn record_meter_reading(payload: MeterReadingPayload) -> Result<MeterReading, Message> {
+    let meter_id = payload.meter_id.trim().to_string();
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("meter_id", &meter_id, MAX_NAME_LENGTH)
+        .non_negative("reading", payload.reading)
+        .check(
+            payload.read_at != 0 && payload.read_at <= time(),
+            "read_at",
+            FieldErrorCode::OutOfRange,
+            "read_at must be set and not in the future",
+        )
+        .text("recorded_by", &payload.recorded_by, MAX_NAME_LENGTH)
+        .finish()?;
+
+    // Readings of one meter must be recorded in order and never run backwards
+    let previous = meter_readings(payload.slaughterhouse_id, &meter_id)
+        .into_iter()
+        .last();
+    if let Some(previous) = previous {
+        Validator::new()
+            .check(
+                previous.meter_type == payload.meter_type,
+                "meter_type",
+                FieldErrorCode::InvalidValue,
+                format!(
+                    "Meter {} is a {} meter",
+                    meter_id,
+                    previous.meter_type.as_str()
+                ),
+            )
+            .check(
+                payload.read_at > previous.read_at,
+                "read_at",
+                FieldErrorCode::OutOfRange,
+                "Reading must be later than the meter's last reading",
+            )
+            .check(
+                payload.reading >= previous.reading,
+                "reading",
+                FieldErrorCode::OutOfRange,
+                format!(
+                    "Reading is below the meter's last reading of {}",
+                    previous.reading
+                ),
+            )
+            .finish()?;
+    }
+
+    let reading_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
+
+    let reading = MeterReading {
+        id: reading_id,
+        slaughterhouse_id: payload.slaughterhouse_id,
+        meter_id,
+        meter_type: payload.meter_type,
+        reading: payload.reading,
+        read_at: payload.read_at,
+        recorded_by: payload.recorded_by,
+    };
+
+    METER_READINGS.with(|readings| {
+        readings.borrow_mut().insert(reading_id, reading.clone());
+    });
+
+    Ok(reading)
+}
+
+// Readings of one meter, oldest first
+fn meter_readings(slaughterhouse_id: u64, meter_id: &str) -> Vec<MeterReading> {
+    let mut readings: Vec<MeterReading> = METER_READINGS.with(|readings| {
+        readings
+            .borrow()
+            .iter()
+            .map(|(_, reading)| reading)
+            .filter(|reading| {
+                reading.slaughterhouse_id == slaughterhouse_id && reading.meter_id == meter_id
+            })
+            .collect()
+    });
+    readings.sort_by_key(|reading| reading.read_at);
+    readings
+}
 
-    let slaughterhouse = Slaughterhouse {
-        id: slaughterhouse_id,
-        name: payload.name,
-        location: payload.location,
-        contact: payload.contact,
-        email: payload.email,
-        capacity: payload.capacity,
-        created_at: time(),
+#[ic_cdk::query]
+fn get_meter_readings(slaughterhouse_id: u64, meter_id: String) -> Vec<MeterReading> {
+    meter_readings(slaughterhouse_id, meter_id.trim())
+}
+
+// Set Discharge Permit
+#[ic_cdk::update]
+fn set_discharge_permit(payload: DischargePermitPayload) -> Result<DischargePermit, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .text("permit_number", &payload.permit_number, MAX_NAME_LENGTH)
+        .positive("bod_limit", payload.bod_limit)
+        .positive("cod_limit", payload.cod_limit)
+        .positive("tss_limit", payload.tss_limit)
+        .finish()?;
+
+    let permit = DischargePermit {
+        slaughterhouse_id: payload.slaughterhouse_id,
+        permit_number: payload.permit_number,
+        bod_limit: payload.bod_limit,
+        cod_limit: payload.cod_limit,
+        tss_limit: payload.tss_limit,
+        updated_at: time(),
     };
 
-    SLAUGHTERHOUSES.with(|houses| {
-        houses
+    DISCHARGE_PERMITS.with(|permits| {
+        permits
             .borrow_mut()
-            .insert(slaughterhouse_id, slaughterhouse.clone());
+            .insert(payload.slaughterhouse_id, permit.clone());
     });
 
-    Ok(slaughterhouse)
+    Ok(permit)
 }
 
-// Register Animal
+// Record Effluent Result, flagging every parameter above its permit limit
 #[ic_cdk::update]
-fn register_animal(payload: RegisterAnimalPayload) -> Result<Animal, Message> {
-    if payload.tag_number.is_empty() || payload.species.is_empty() {
-        return Err(Message::InvalidPayload(
-            "Missing required fields".to_string(),
-        ));
-    }
-
-    let slaughterhouse_exists =
-        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&payload.slaughterhouse_id));
-    if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
-    }
+fn record_effluent_result(payload: EffluentResultPayload) -> Result<EffluentResult, Message> {
+    Validator::new()
+        .reference(
+            "slaughterhouse_id",
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        )
+        .check(
+            payload.sampled_at != 0 && payload.sampled_at <= time(),
+            "sampled_at",
+            FieldErrorCode::OutOfRange,
+            "sampled_at must be set and not in the future",
+        )
+        .text("lab_reference", &payload.lab_reference, MAX_NAME_LENGTH)
+        .non_negative("bod", payload.bod)
+        .non_negative("cod", payload.cod)
+        .non_negative("tss", payload.tss)
+        .finish()?;
+
+    let permit = DISCHARGE_PERMITS
+        .with(|permits| permits.borrow().get(&payload.slaughterhouse_id))
+        .ok_or_else(|| {
+            Message::not_found_by(
+                "discharge_permit",
+                format!(
+                    "Slaughterhouse {} has no discharge permit",
+                    payload.slaughterhouse_id
+                ),
+            )
+        })?;
+
+    let exceedances: Vec<String> = [
+        ("BOD", payload.bod, permit.bod_limit),
+        ("COD", payload.cod, permit.cod_limit),
+        ("TSS", payload.tss, permit.tss_limit),
+    ]
+    .iter()
+    .filter(|(_, value, limit)| value > limit)
+    .map(|(parameter, value, limit)| {
+        format!(
+            "{} of {:.1} mg/L exceeds limit of {:.1} mg/L",
+            parameter, value, limit
+        )
+    })
+    .collect();
 
-    let animal_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+    let result_id = ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter
+            .borrow_mut()
+            .set(current_value + 1)
+            .expect("Counter increment failed");
+        current_value
+    });
 
-    let animal = Animal {
-        id: animal_id,
+    let result = EffluentResult {
+        id: result_id,
         slaughterhouse_id: payload.slaughterhouse_id,
-        tag_number: payload.tag_number,
-        species: payload.species,
-        weight: payload.weight,
-        arrival_time: time(),
-        status: "received".to_string(),
+        permit_number: permit.permit_number,
+        sampled_at: payload.sampled_at,
+        lab_reference: payload.lab_reference,
+        bod: payload.bod,
+        cod: payload.cod,
+        tss: payload.tss,
+        compliant: exceedances.is_empty(),
+        exceedances,
+        recorded_at: time(),
     };
 
-    ANIMALS.with(|animals| {
-        animals.borrow_mut().insert(animal_id, animal.clone());
+    EFFLUENT_RESULTS.with(|results| {
+        results.borrow_mut().insert(result_id, result.clone());
     });
 
-    Ok(animal)
+    Ok(result)
 }
 
-// Create Meat Product
-#[ic_cdk::update]
-fn create_meat_product(payload: CreateMeatProductPayload) -> Result<MeatProduct, Message> {
-    if payload.product_type.is_empty() || payload.weight <= 0.0 || payload.price_per_kg <= 0.0 {
-        return Err(Message::InvalidPayload("Invalid product data".to_string()));
-    }
+#[ic_cdk::query]
+fn get_effluent_exceedances(slaughterhouse_id: u64) -> Vec<EffluentResult> {
+    EFFLUENT_RESULTS.with(|results| {
+        results
+            .borrow()
+            .iter()
+            .map(|(_, result)| result)
+            .filter(|result| result.slaughterhouse_id == slaughterhouse_id && !result.compliant)
+            .collect()
+    })
+}
 
-    let animal_exists = ANIMALS.with(|animals| animals.borrow().contains_key(&payload.animal_id));
-    if !animal_exists {
-        return Err(Message::NotFound("Animal not found".to_string()));
-    }
+// Query functions for analytics and reporting
 
+#[ic_cdk::query]
+fn get_quality_metrics(
+    slaughterhouse_id: u64,
+    start_date: u64,
+    end_date: u64,
+) -> Result<QualityMetrics, Message> {
     let slaughterhouse_exists =
-        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&payload.slaughterhouse_id));
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
     if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
     }
 
-    let meat_product_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
-
-    let total_price = payload.weight * payload.price_per_kg;
-
-    let meat_product = MeatProduct {
-        id: meat_product_id,
-        animal_id: payload.animal_id,
-        slaughterhouse_id: payload.slaughterhouse_id,
-        product_type: payload.product_type,
-        weight: payload.weight,
-        price_per_kg: payload.price_per_kg,
-        total_price,
-        status: "in-stock".to_string(),
-        created_at: time(),
-    };
+    let mut total_inspections = 0;
+    let mut passed_inspections = 0;
+    let mut avg_temperature = 0.0;
+    let mut avg_ph_level = 0.0;
+    let mut inspections_in_range = Vec::new();
 
-    MEAT_PRODUCTS.with(|products| {
-        products
-            .borrow_mut()
-            .insert(meat_product_id, meat_product.clone());
+    QUALITY_INSPECTIONS.with(|inspections| {
+        inspections
+            .borrow()
+            .iter()
+            .filter(|(_, inspection)| {
+                let animal = ANIMALS.with(|animals| {
+                    animals
+                        .borrow()
+                        .get(&inspection.animal_id)
+                        .map(|animal| animal.slaughterhouse_id == slaughterhouse_id)
+                        .unwrap_or(false)
+                });
+                animal
+                    && inspection.inspection_date >= start_date
+                    && inspection.inspection_date <= end_date
+            })
+            .for_each(|(_, inspection)| {
+                total_inspections += 1;
+                if inspection.passed {
+                    passed_inspections += 1;
+                }
+                avg_temperature += inspection.temperature;
+                avg_ph_level += inspection.ph_level;
+                inspections_in_range.push(inspection.clone());
+            });
     });
 
-    Ok(meat_product)
+    if total_inspections > 0 {
+        avg_temperature /= total_inspections as f64;
+        avg_ph_level /= total_inspections as f64;
+    }
+
+    Ok(QualityMetrics {
+        total_inspections,
+        passed_inspections,
+        failure_rate: if total_inspections > 0 {
+            ((total_inspections - passed_inspections) as f64 / total_inspections as f64 * 100.0)
+                .round()
+        } else {
+            0.0
+        },
+        average_temperature: round2(avg_temperature),
+        average_ph_level: round2(avg_ph_level),
+        inspections: inspections_in_range,
+    })
 }
 
-// Record Expense
-#[ic_cdk::update]
-fn record_expense(payload: RecordExpensePayload) -> Result<Expense, Message> {
-    if payload.amount <= 0.0 {
-        return Err(Message::InvalidPayload(
-            "Invalid expense amount".to_string(),
-        ));
-    }
+// Per-question results of the inspections recorded against a template version
+#[ic_cdk::query]
+fn get_question_aggregates(template_id: u64) -> Result<Vec<QuestionAggregate>, Message> {
+    let template = INSPECTION_TEMPLATES
+        .with(|templates| templates.borrow().get(&template_id))
+        .ok_or_else(|| Message::not_found("inspection_template", template_id))?;
+
+    let mut aggregates: Vec<QuestionAggregate> = template
+        .questions
+        .iter()
+        .map(|question| QuestionAggregate {
+            question_code: question.code.clone(),
+            text: question.text.clone(),
+            answer_type: question.answer_type,
+            ..Default::default()
+        })
+        .collect();
 
-    let slaughterhouse_exists =
-        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&payload.slaughterhouse_id));
-    if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
+    INSPECTION_RESPONSES.with(|responses| {
+        responses
+            .borrow()
+            .iter()
+            .filter(|(_, response)| response.template_id == template_id)
+            .for_each(|(_, response)| {
+                for answer in &response.answers {
+                    let aggregate = match aggregates
+                        .iter_mut()
+                        .find(|aggregate| aggregate.question_code == answer.question_code)
+                    {
+                        Some(aggregate) => aggregate,
+                        None => continue,
+                    };
+
+                    if aggregate.responses == 0 || answer.value < aggregate.min_value {
+                        aggregate.min_value = answer.value;
+                    }
+                    if aggregate.responses == 0 || answer.value > aggregate.max_value {
+                        aggregate.max_value = answer.value;
+                    }
+                    aggregate.responses += 1;
+                    if !answer.acceptable {
+                        aggregate.failed += 1;
+                    }
+                    match answer.answer_type {
+                        AnswerType::YesNo if answer.yes_no => aggregate.yes_count += 1,
+                        AnswerType::YesNo => aggregate.no_count += 1,
+                        AnswerType::Photo => aggregate.photo_count += 1,
+                        AnswerType::Score | AnswerType::Numeric => {
+                            aggregate.average_value += answer.value
+                        }
+                    }
+                }
+            });
+    });
+
+    for aggregate in &mut aggregates {
+        let ranged = matches!(
+            aggregate.answer_type,
+            AnswerType::Score | AnswerType::Numeric
+        );
+
+        if !ranged {
+            aggregate.min_value = 0.0;
+            aggregate.max_value = 0.0;
+        } else if aggregate.responses > 0 {
+            aggregate.average_value /= aggregate.responses as f64;
+        }
     }
 
-    let expense_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+    Ok(aggregates)
+}
 
-    let expense = Expense {
-        id: expense_id,
-        slaughterhouse_id: payload.slaughterhouse_id,
-        date: time(),
-        category: payload.category,
-        amount: payload.amount,
-        description: payload.description,
-    };
+#[ic_cdk::query]
+fn get_maintenance_analytics(
+    slaughterhouse_id: u64,
+    start_date: u64,
+    end_date: u64,
+) -> Result<MaintenanceAnalytics, Message> {
+    let mut total_maintenance_cost = 0.0;
+    let mut maintenance_by_type: HashMap<String, u32> = HashMap::new();
+    let mut equipment_history: HashMap<String, Vec<MaintenanceRecord>> = HashMap::new();
+    let mut pending_maintenance = Vec::new();
 
-    EXPENSES.with(|expenses| {
-        expenses.borrow_mut().insert(expense_id, expense.clone());
+    MAINTENANCE_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| {
+                record.slaughterhouse_id == slaughterhouse_id
+                    && record.date >= start_date
+                    && record.date <= end_date
+            })
+            .for_each(|(_, record)| {
+                total_maintenance_cost += record.cost;
+                *maintenance_by_type
+                    .entry(record.maintenance_type.as_str().to_string())
+                    .or_insert(0) += 1;
+                equipment_history
+                    .entry(record.equipment_name.clone())
+                    .or_insert_with(Vec::new)
+                    .push(record.clone());
+
+                if record.status == MaintenanceStatus::Scheduled
+                    || record.status == MaintenanceStatus::InProgress
+                {
+                    pending_maintenance.push(record.clone());
+                }
+            });
     });
 
-    Ok(expense)
+    let mut equipment_reliability: HashMap<String, f64> = HashMap::new();
+    for (equipment, history) in &equipment_history {
+        let total_records = history.len() as f64;
+        let emergency_repairs = history
+            .iter()
+            .filter(|record| record.maintenance_type == MaintenanceType::Emergency)
+            .count() as f64;
+        let reliability = ((total_records - emergency_repairs) / total_records * 100.0).round();
+        equipment_reliability.insert(equipment.clone(), reliability);
+    }
+
+    Ok(MaintenanceAnalytics {
+        total_maintenance_cost,
+        maintenance_by_type,
+        equipment_reliability,
+        pending_maintenance,
+        equipment_history,
+    })
 }
 
-// Calculate Total Revenue
 #[ic_cdk::query]
-fn calculate_total_revenue(slaughterhouse_id: u64) -> Result<f64, Message> {
-    let slaughterhouse_exists =
-        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
-    if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
-    }
+fn get_inventory_analytics(slaughterhouse_id: u64) -> Result<InventoryAnalytics, Message> {
+    let mut product_counts: HashMap<String, u32> = HashMap::new();
+    let mut total_value = 0.0;
+    let mut products_by_status: HashMap<String, Vec<MeatProduct>> = HashMap::new();
+    let mut low_stock_items = Vec::new();
+    let mut expired_items = Vec::new();
+    let now = time();
 
-    let total_revenue: f64 = MEAT_PRODUCTS.with(|products| {
+    MEAT_PRODUCTS.with(|products| {
         products
             .borrow()
             .iter()
             .filter(|(_, product)| product.slaughterhouse_id == slaughterhouse_id)
-            .map(|(_, product)| product.total_price)
-            .sum()
+            .for_each(|(_, product)| {
+                *product_counts
+                    .entry(product.product_type.clone())
+                    .or_insert(0) += 1;
+                total_value += product.total_price;
+
+                products_by_status
+                    .entry(product.status.as_str().to_string())
+                    .or_insert_with(Vec::new)
+                    .push(product.clone());
+
+                // Consider products with quantity below 10 as low stock
+                if product.weight < 10.0 {
+                    low_stock_items.push(product.clone());
+                }
+
+                if product.status == ProductStatus::InStock && is_past_shelf_life(&product, now) {
+                    expired_items.push(product.clone());
+                }
+            });
     });
 
-    Ok(total_revenue)
+    Ok(InventoryAnalytics {
+        product_counts,
+        total_inventory_value: total_value,
+        products_by_status,
+        low_stock_items,
+        expired_items,
+    })
 }
 
-// Calculate Total Expenses
 #[ic_cdk::query]
-fn calculate_total_expenses(slaughterhouse_id: u64) -> Result<f64, Message> {
+fn get_environmental_analytics(
+    slaughterhouse_id: u64,
+    start_date: u64,
+    end_date: u64,
+) -> Result<EnvironmentalAnalytics, Message> {
     let slaughterhouse_exists =
         SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
     if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
     }
 
-    let total_expenses: f64 = EXPENSES.with(|expenses| {
-        expenses
+    let animals_processed = animals_processed(slaughterhouse_id, start_date, end_date);
+    let product_weight: f64 = MEAT_PRODUCTS.with(|products| {
+        products
             .borrow()
             .iter()
-            .filter(|(_, expense)| expense.slaughterhouse_id == slaughterhouse_id)
-            .map(|(_, expense)| expense.amount)
+            .filter(|(_, product)| {
+                product.slaughterhouse_id == slaughterhouse_id
+                    && product.created_at >= start_date
+                    && product.created_at <= end_date
+            })
+            .map(|(_, product)| product.weight)
             .sum()
     });
 
-    Ok(total_expenses)
-}
-
-#[ic_cdk::update]
-fn perform_quality_inspection(
-    payload: QualityInspectionPayload,
-) -> Result<QualityInspection, Message> {
-    // Validate animal exists
-    let animal_exists = ANIMALS.with(|animals| animals.borrow().contains_key(&payload.animal_id));
-    if !animal_exists {
-        return Err(Message::NotFound("Animal not found".to_string()));
-    }
-
-    let inspection_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter
-            .borrow_mut()
-            .set(current_value + 1)
-            .expect("Counter increment failed");
-        current_value
-    });
-
-    let inspection = QualityInspection {
-        id: inspection_id,
-        animal_id: payload.animal_id,
-        inspector_name: payload.inspector_name,
-        inspection_date: time(),
-        temperature: payload.temperature,
-        ph_level: payload.ph_level,
-        visual_inspection: payload.visual_inspection,
-        passed: payload.passed,
-        notes: payload.notes,
-    };
-
-    QUALITY_INSPECTIONS.with(|inspections| {
-        inspections
-            .borrow_mut()
-            .insert(inspection_id, inspection.clone());
+    // Consumption of each meter is the rise between consecutive readings taken in the period
+    let mut meters: BTreeMap<String, Vec<MeterReading>> = BTreeMap::new();
+    METER_READINGS.with(|readings| {
+        readings
+            .borrow()
+            .iter()
+            .filter(|(_, reading)| reading.slaughterhouse_id == slaughterhouse_id)
+            .for_each(|(_, reading)| {
+                meters
+                    .entry(reading.meter_id.clone())
+                    .or_default()
+                    .push(reading);
+            });
     });
-
-    Ok(inspection)
-}
-
-#[ic_cdk::update]
-fn register_employee(payload: EmployeePayload) -> Result<Employee, Message> {
-    if payload.name.is_empty() || payload.role.is_empty() {
-        return Err(Message::InvalidPayload(
-            "Missing required fields".to_string(),
-        ));
-    }
-
-    let slaughterhouse_exists =
-        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&payload.slaughterhouse_id));
-    if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
+    let mut consumption_by_type: HashMap<&str, f64> = HashMap::new();
+    for readings in meters.values_mut() {
+        readings.sort_by_key(|reading| reading.read_at);
+        for pair in readings.windows(2) {
+            if pair[1].read_at >= start_date && pair[1].read_at <= end_date {
+                *consumption_by_type
+                    .entry(pair[1].meter_type.as_str())
+                    .or_insert(0.0) += pair[1].reading - pair[0].reading;
+            }
+        }
     }
-
-    let employee_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter
-            .borrow_mut()
-            .set(current_value + 1)
-            .expect("Counter increment failed");
-        current_value
-    });
-
-    let employee = Employee {
-        id: employee_id,
-        slaughterhouse_id: payload.slaughterhouse_id,
-        name: payload.name,
-        role: payload.role,
-        certification: payload.certification,
-        hire_date: time(),
-        contact: payload.contact,
-        status: "active".to_string(),
+    let consumption = |meter_type: MeterType| -> f64 {
+        consumption_by_type
+            .get(meter_type.as_str())
+            .copied()
+            .unwrap_or(0.0)
     };
-
-    EMPLOYEES.with(|employees| {
-        employees.borrow_mut().insert(employee_id, employee.clone());
+    let water_litres = consumption(MeterType::Water);
+    let energy_kwh = consumption(MeterType::Electricity) + consumption(MeterType::Gas);
+    let emissions_kg_co2e: f64 = METER_TYPES
+        .iter()
+        .map(|(meter_type, _, factor)| consumption(*meter_type) * factor)
+        .sum();
+
+    let mut waste_kg = 0.0;
+    let mut waste_kg_by_category: HashMap<String, f64> = HashMap::new();
+    WASTE_RECORDS.with(|records| {
+        records
+            .borrow()
+            .iter()
+            .filter(|(_, record)| {
+                record.slaughterhouse_id == slaughterhouse_id
+                    && record.disposal_date >= start_date
+                    && record.disposal_date <= end_date
+            })
+            .for_each(|(_, record)| {
+                waste_kg += record.quantity;
+                let category = record
+                    .category
+                    .map(|category| category.label())
+                    .unwrap_or("Uncategorised");
+                *waste_kg_by_category
+                    .entry(category.to_string())
+                    .or_insert(0.0) += record.quantity;
+            });
     });
 
-    Ok(employee)
-}
-
-#[ic_cdk::update]
-fn schedule_maintenance(payload: MaintenancePayload) -> Result<MaintenanceRecord, Message> {
-    if payload.equipment_name.is_empty() || payload.maintenance_type.is_empty() {
-        return Err(Message::InvalidPayload(
-            "Missing required fields".to_string(),
-        ));
-    }
-
-    let maintenance_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter
-            .borrow_mut()
-            .set(current_value + 1)
-            .expect("Counter increment failed");
-        current_value
+    let (effluent_results, effluent_exceedances) = EFFLUENT_RESULTS.with(|results| {
+        results
+            .borrow()
+            .iter()
+            .filter(|(_, result)| {
+                result.slaughterhouse_id == slaughterhouse_id
+                    && result.sampled_at >= start_date
+                    && result.sampled_at <= end_date
+            })
+            .fold((0, 0), |(total, exceeded), (_, result)| {
+                (total + 1, exceeded + u32::from(!result.compliant))
+            })
     });
 
-    let record = MaintenanceRecord {
-        id: maintenance_id,
-        slaughterhouse_id: payload.slaughterhouse_id,
-        equipment_name: payload.equipment_name,
-        maintenance_type: payload.maintenance_type,
-        cost: payload.estimated_cost,
-        date: payload.scheduled_date,
-        next_maintenance_date: payload.scheduled_date + 7_884_000, // Default to 3 months
-        performed_by: "".to_string(),
-        status: "scheduled".to_string(),
-        notes: payload.notes,
+    let per_head = |total: f64| -> f64 {
+        if animals_processed > 0 {
+            total / animals_processed as f64
+        } else {
+            0.0
+        }
+    };
+    let per_kg = |total: f64| -> f64 {
+        if product_weight > 0.0 {
+            total / product_weight
+        } else {
+            0.0
+        }
     };
 
-    MAINTENANCE_RECORDS.with(|records| {
-        records.borrow_mut().insert(maintenance_id, record.clone());
-    });
+    Ok(EnvironmentalAnalytics {
+        animals_processed,
+        product_weight,
+        water_litres,
+        energy_kwh,
+        water_litres_per_head: per_head(water_litres),
+        water_litres_per_kg: per_kg(water_litres),
+        energy_kwh_per_head: per_head(energy_kwh),
+        energy_kwh_per_kg: per_kg(energy_kwh),
+        waste_kg,
+        waste_kg_per_head: per_head(waste_kg),
+        waste_kg_by_category,
+        emissions_kg_co2e,
+        emissions_kg_co2e_per_head: per_head(emissions_kg_co2e),
+        effluent_results,
+        effluent_exceedances,
+    })
+}
 
-    Ok(record)
+// Processed animals that arrived within the period
+fn animals_processed(slaughterhouse_id: u64, start_date: u64, end_date: u64) -> u32 {
+    ANIMALS.with(|animals| {
+        animals
+            .borrow()
+            .iter()
+            .filter(|(_, animal)| {
+                animal.slaughterhouse_id == slaughterhouse_id
+                    && animal.status == AnimalStatus::Processed
+                    && animal.arrival_time >= start_date
+                    && animal.arrival_time <= end_date
+            })
+            .count() as u32
+    })
 }
 
-#[ic_cdk::update]
-fn create_shipment(payload: ShipmentPayload) -> Result<Shipment, Message> {
-    // Validate all products exist
-    for product_id in &payload.product_ids {
-        let product_exists =
-            MEAT_PRODUCTS.with(|products| products.borrow().contains_key(product_id));
-        if !product_exists {
-            return Err(Message::NotFound(format!(
-                "Product {} not found",
-                product_id
-            )));
+fn is_past_shelf_life(product: &MeatProduct, now: u64) -> bool {
+    let shelf_life_days = ANIMALS
+        .with(|animals| animals.borrow().get(&product.animal_id))
+        .and_then(|animal| find_species(&animal.species))
+        .map(|species| species.shelf_life_days);
+
+    match shelf_life_days {
+        Some(days) => {
+            product
+                .created_at
+                .saturating_add((days as u64).saturating_mul(NANOS_PER_DAY))
+                < now
         }
+        None => false,
     }
-
-    let shipment_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter
-            .borrow_mut()
-            .set(current_value + 1)
-            .expect("Counter increment failed");
-        current_value
-    });
-
-    let tracking_number = format!("SH{:0>6}", shipment_id);
-
-    let shipment = Shipment {
-        id: shipment_id,
-        slaughterhouse_id: payload.slaughterhouse_id,
-        product_ids: payload.product_ids,
-        destination: payload.destination,
-        shipping_date: time(),
-        expected_delivery: payload.expected_delivery,
-        temperature_log: Vec::new(),
-        status: "preparing".to_string(),
-        tracking_number,
-    };
-
-    SHIPMENTS.with(|shipments| {
-        shipments.borrow_mut().insert(shipment_id, shipment.clone());
-    });
-
-    Ok(shipment)
 }
 
 #[ic_cdk::query]
-fn generate_financial_analytics(slaughterhouse_id: u64) -> Result<FinancialMetrics, Message> {
+fn get_welfare_kpis(
+    slaughterhouse_id: u64,
+    start_date: u64,
+    end_date: u64,
+) -> Result<WelfareKpis, Message> {
     let slaughterhouse_exists =
         SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
     if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
     }
 
-    let total_revenue = calculate_total_revenue(slaughterhouse_id)?;
-    let total_expenses = calculate_total_expenses(slaughterhouse_id)?;
-
-    let maintenance_costs: f64 = MAINTENANCE_RECORDS.with(|records| {
-        records
+    let animals_received = ANIMALS.with(|animals| {
+        animals
             .borrow()
             .iter()
-            .filter(|(_, record)| record.slaughterhouse_id == slaughterhouse_id)
-            .map(|(_, record)| record.cost)
-            .sum()
+            .filter(|(_, animal)| {
+                animal.slaughterhouse_id == slaughterhouse_id
+                    && animal.arrival_time >= start_date
+                    && animal.arrival_time <= end_date
+            })
+            .count() as u32
     });
 
-    let waste_management_costs: f64 = WASTE_RECORDS.with(|records| {
-        records
-            .borrow()
-            .iter()
-            .filter(|(_, record)| record.slaughterhouse_id == slaughterhouse_id)
-            .map(|(_, record)| record.cost)
-            .sum()
-    });
+    let mut animals_stunned = 0;
+    let mut re_stun_events = 0;
+    let mut dead_on_arrival = 0;
+    let mut total_lairage_hours = 0.0;
+    let mut transport_events = 0;
+    let mut total_transport_hours = 0.0;
+    let mut flagged_events = Vec::new();
 
-    let mut revenue_by_product: HashMap<String, f64> = HashMap::new();
-    MEAT_PRODUCTS.with(|products| {
-        products
+    WELFARE_EVENTS.with(|events| {
+        events
             .borrow()
             .iter()
-            .filter(|(_, product)| product.slaughterhouse_id == slaughterhouse_id)
-            .for_each(|(_, product)| {
-                *revenue_by_product
-                    .entry(product.product_type.clone())
-                    .or_insert(0.0) += product.total_price;
+            .filter(|(_, event)| {
+                event.slaughterhouse_id == slaughterhouse_id
+                    && event.event_time >= start_date
+                    && event.event_time <= end_date
+            })
+            .for_each(|(_, event)| {
+                match event.event_type {
+                    WelfareEventType::Stun => {
+                        animals_stunned += 1;
+                        total_lairage_hours += event.duration_hours;
+                    }
+                    WelfareEventType::ReStun => re_stun_events += 1,
+                    WelfareEventType::DeadOnArrival => dead_on_arrival += 1,
+                    WelfareEventType::Transport => {
+                        transport_events += 1;
+                        total_transport_hours += event.duration_hours;
+                    }
+                    _ => {}
+                }
+                if event.flagged {
+                    flagged_events.push(event.clone());
+                }
             });
     });
 
-    let metrics = FinancialMetrics {
-        total_revenue,
-        total_expenses,
-        profit_margin: ((total_revenue - total_expenses) / total_revenue * 100.0).max(0.0),
-        operating_costs: total_expenses - maintenance_costs - waste_management_costs,
-        maintenance_costs,
-        labor_costs: 0.0, // Would need to implement payroll system
-        waste_management_costs,
-        revenue_by_product,
-        expenses_by_category: HashMap::new(), // Would need to implement expense categorization
-    };
+    let lairage_overruns = lairage_overruns(slaughterhouse_id, time());
 
-    Ok(metrics)
+    Ok(WelfareKpis {
+        animals_received,
+        animals_stunned,
+        re_stun_events,
+        re_stun_rate: if animals_stunned > 0 {
+            re_stun_events as f64 / animals_stunned as f64 * 100.0
+        } else {
+            0.0
+        },
+        average_lairage_hours: if animals_stunned > 0 {
+            total_lairage_hours / animals_stunned as f64
+        } else {
+            0.0
+        },
+        average_transport_hours: if transport_events > 0 {
+            total_transport_hours / transport_events as f64
+        } else {
+            0.0
+        },
+        dead_on_arrival,
+        mortality_on_arrival_rate: if animals_received > 0 {
+            dead_on_arrival as f64 / animals_received as f64 * 100.0
+        } else {
+            0.0
+        },
+        flagged_events,
+        lairage_overruns,
+    })
 }
 
-#[ic_cdk::update]
-fn manage_waste_disposal(
-    slaughterhouse_id: u64,
-    waste_type: String,
-    quantity: f64,
-    disposal_method: String,
-    cost: f64,
-    handled_by: String,
-) -> Result<WasteRecord, Message> {
-    let waste_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter
-            .borrow_mut()
-            .set(current_value + 1)
-            .expect("Counter increment failed");
-        current_value
-    });
-
-    let record = WasteRecord {
-        id: waste_id,
-        slaughterhouse_id,
-        waste_type,
-        quantity,
-        disposal_method,
-        disposal_date: time(),
-        handled_by,
-        cost,
-    };
-
-    WASTE_RECORDS.with(|records| {
-        records.borrow_mut().insert(waste_id, record.clone());
+// Animals still in lairage past the limit, which no stun event has flagged yet
+fn lairage_overruns(slaughterhouse_id: u64, now: u64) -> Vec<u64> {
+    let limits = welfare_limits_for(slaughterhouse_id);
+    let stunned: Vec<u64> = WELFARE_EVENTS.with(|events| {
+        events
+            .borrow()
+            .iter()
+            .filter(|(_, event)| {
+                event.slaughterhouse_id == slaughterhouse_id
+                    && event.event_type == WelfareEventType::Stun
+            })
+            .map(|(_, event)| event.animal_id)
+            .collect()
     });
 
-    Ok(record)
+    ANIMALS.with(|animals| {
+        animals
+            .borrow()
+            .iter()
+            .filter(|(_, animal)| {
+                animal.slaughterhouse_id == slaughterhouse_id
+                    && animal.status == AnimalStatus::Received
+                    && !stunned.contains(&animal.id)
+                    && now.saturating_sub(animal.arrival_time) as f64 / NANOS_PER_HOUR as f64
+                        > limits.max_lairage_hours
+            })
+            .map(|(id, _)| id)
+            .collect()
+    })
 }
 
-// Query functions for analytics and reporting
-
 #[ic_cdk::query]
-fn get_quality_metrics(
+fn get_grade_distribution(
     slaughterhouse_id: u64,
     start_date: u64,
     end_date: u64,
-) -> Result<QualityMetrics, Message> {
+) -> Result<GradeDistribution, Message> {
     let slaughterhouse_exists =
         SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
     if !slaughterhouse_exists {
-        return Err(Message::NotFound("Slaughterhouse not found".to_string()));
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
     }
 
-    let mut total_inspections = 0;
-    let mut passed_inspections = 0;
-    let mut avg_temperature = 0.0;
-    let mut avg_ph_level = 0.0;
-    let mut inspections_in_range = Vec::new();
+    let mut total_graded = 0;
+    let mut total_weight = 0.0;
+    let mut grades: HashMap<String, u32> = HashMap::new();
+    let mut grades_by_supplier: HashMap<u64, HashMap<String, u32>> = HashMap::new();
 
-    QUALITY_INSPECTIONS.with(|inspections| {
-        inspections
+    CARCASS_GRADES.with(|carcass_grades| {
+        carcass_grades
             .borrow()
             .iter()
-            .filter(|(_, inspection)| {
-                let animal = ANIMALS.with(|animals| {
-                    animals
-                        .borrow()
-                        .get(&inspection.animal_id)
-                        .map(|animal| animal.slaughterhouse_id == slaughterhouse_id)
-                        .unwrap_or(false)
-                });
-                animal
-                    && inspection.inspection_date >= start_date
-                    && inspection.inspection_date <= end_date
+            .filter(|(_, grade)| {
+                grade.slaughterhouse_id == slaughterhouse_id
+                    && grade.graded_at >= start_date
+                    && grade.graded_at <= end_date
             })
-            .for_each(|(_, inspection)| {
-                total_inspections += 1;
-                if inspection.passed {
-                    passed_inspections += 1;
+            .for_each(|(_, grade)| {
+                total_graded += 1;
+                total_weight += grade.carcass_weight;
+                *grades.entry(grade.grade.clone()).or_insert(0) += 1;
+                if let Some(supplier_id) = animal_supplier_id(grade.animal_id) {
+                    *grades_by_supplier
+                        .entry(supplier_id)
+                        .or_default()
+                        .entry(grade.grade.clone())
+                        .or_insert(0) += 1;
                 }
-                avg_temperature += inspection.temperature;
-                avg_ph_level += inspection.ph_level;
-                inspections_in_range.push(inspection.clone());
             });
     });
 
-    if total_inspections > 0 {
-        avg_temperature /= total_inspections as f64;
-        avg_ph_level /= total_inspections as f64;
-    }
-
-    Ok(QualityMetrics {
-        total_inspections,
-        passed_inspections,
-        failure_rate: if total_inspections > 0 {
-            ((total_inspections - passed_inspections) as f64 / total_inspections as f64 * 100.0)
-                .round()
+    Ok(GradeDistribution {
+        total_graded,
+        average_carcass_weight: if total_graded > 0 {
+            total_weight / total_graded as f64
         } else {
             0.0
         },
-        average_temperature: avg_temperature.round(),
-        average_ph_level: avg_ph_level.round(),
-        inspections: inspections_in_range,
+        grades,
+        grades_by_supplier,
     })
 }
 
+// Monitoring status of each critical control point, flagging checks that are overdue
 #[ic_cdk::query]
-fn get_maintenance_analytics(
-    slaughterhouse_id: u64,
-    start_date: u64,
-    end_date: u64,
-) -> Result<MaintenanceAnalytics, Message> {
-    let mut total_maintenance_cost = 0.0;
-    let mut maintenance_by_type: HashMap<String, u32> = HashMap::new();
-    let mut equipment_history: HashMap<String, Vec<MaintenanceRecord>> = HashMap::new();
-    let mut pending_maintenance = Vec::new();
+fn get_ccp_status(slaughterhouse_id: u64) -> Result<Vec<CcpStatus>, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
 
-    MAINTENANCE_RECORDS.with(|records| {
-        records
-            .borrow()
+    let now = time();
+    let ccps: Vec<CriticalControlPoint> = CRITICAL_CONTROL_POINTS.with(|ccps| {
+        ccps.borrow()
             .iter()
-            .filter(|(_, record)| {
-                record.slaughterhouse_id == slaughterhouse_id
-                    && record.date >= start_date
-                    && record.date <= end_date
-            })
-            .for_each(|(_, record)| {
-                total_maintenance_cost += record.cost;
-                *maintenance_by_type
-                    .entry(record.maintenance_type.clone())
-                    .or_insert(0) += 1;
-                equipment_history
-                    .entry(record.equipment_name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(record.clone());
+            .filter(|(_, ccp)| ccp.slaughterhouse_id == slaughterhouse_id)
+            .map(|(_, ccp)| ccp)
+            .collect()
+    });
 
-                if record.status == "scheduled" || record.status == "in-progress" {
-                    pending_maintenance.push(record.clone());
-                }
+    Ok(ccps
+        .into_iter()
+        .map(|ccp| {
+            let last_monitored_at = CCP_MONITORING_LOGS.with(|logs| {
+                logs.borrow()
+                    .iter()
+                    .filter(|(_, log)| log.ccp_id == ccp.id)
+                    .map(|(_, log)| log.recorded_at)
+                    .max()
+                    .unwrap_or(0)
+            });
+            let open_deviations = CCP_DEVIATIONS.with(|deviations| {
+                deviations
+                    .borrow()
+                    .iter()
+                    .filter(|(_, deviation)| {
+                        deviation.ccp_id == ccp.id && deviation.status == DeviationStatus::Open
+                    })
+                    .count() as u64
             });
-    });
 
-    let mut equipment_reliability: HashMap<String, f64> = HashMap::new();
-    for (equipment, history) in &equipment_history {
-        let total_records = history.len() as f64;
-        let emergency_repairs = history
+            // Never-monitored points are due from the moment they were defined
+            let next_due_at = last_monitored_at.max(ccp.created_at).saturating_add(
+                ccp.monitoring_interval_minutes
+                    .saturating_mul(NANOS_PER_HOUR / 60),
+            );
+
+            CcpStatus {
+                ccp,
+                last_monitored_at,
+                next_due_at,
+                overdue: now > next_due_at,
+                open_deviations,
+            }
+        })
+        .collect())
+}
+
+// Rounds analytics values to two decimals
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+// Percentile of sorted values with linear interpolation
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let position = fraction * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+fn summarize(values: &[f64]) -> SummaryStatistics {
+    if values.is_empty() {
+        return SummaryStatistics::default();
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let count = sorted.len() as f64;
+    let mean = sorted.iter().sum::<f64>() / count;
+    let std_dev = if sorted.len() > 1 {
+        (sorted
             .iter()
-            .filter(|record| record.maintenance_type == "emergency")
-            .count() as f64;
-        let reliability = ((total_records - emergency_repairs) / total_records * 100.0).round();
-        equipment_reliability.insert(equipment.clone(), reliability);
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / (count - 1.0))
+            .sqrt()
+    } else {
+        0.0
+    };
+
+    SummaryStatistics {
+        count: sorted.len() as u64,
+        mean,
+        std_dev,
+        min: sorted[0],
+        max: sorted[sorted.len() - 1],
+        p5: percentile(&sorted, 0.05),
+        median: percentile(&sorted, 0.5),
+        p95: percentile(&sorted, 0.95),
     }
+}
 
-    Ok(MaintenanceAnalytics {
-        total_maintenance_cost,
-        maintenance_by_type,
-        equipment_reliability,
-        pending_maintenance,
-        equipment_history,
+fn group_statistics(groups: BTreeMap<String, Vec<f64>>) -> Vec<GroupStatistics> {
+    groups
+        .into_iter()
+        .map(|(group, values)| GroupStatistics {
+            group,
+            statistics: summarize(&values),
+        })
+        .collect()
+}
+
+// Western Electric rules over the daily means, zones measured in sigmas of the mean
+fn western_electric_violations(
+    daily: &[DailyStatistics],
+    center_line: f64,
+    sigma: f64,
+) -> Vec<ControlRuleViolation> {
+    let mut violations = Vec::new();
+    if sigma <= 0.0 {
+        return violations;
+    }
+    let zones: Vec<f64> = daily
+        .iter()
+        .map(|day| (day.statistics.mean - center_line) / sigma)
+        .collect();
+    // Count of points in the window beyond the zone on the side of the last point
+    let beyond = |window: &[f64], zone: f64| {
+        let side = window[window.len() - 1].signum();
+        window
+            .iter()
+            .filter(|z| z.signum() == side && z.abs() > zone)
+            .count()
+    };
+
+    for (index, day) in daily.iter().enumerate() {
+        let mut flag = |rule: u8, description: &str| {
+            violations.push(ControlRuleViolation {
+                rule,
+                day_start: day.day_start,
+                description: description.to_string(),
+            })
+        };
+        if zones[index].abs() > 3.0 {
+            flag(1, "One point beyond 3 sigma");
+        }
+        if index >= 2 && zones[index].abs() > 2.0 && beyond(&zones[index - 2..=index], 2.0) >= 2 {
+            flag(
+                2,
+                "Two of three consecutive points beyond 2 sigma on one side",
+            );
+        }
+        if index >= 4 && zones[index].abs() > 1.0 && beyond(&zones[index - 4..=index], 1.0) >= 4 {
+            flag(
+                3,
+                "Four of five consecutive points beyond 1 sigma on one side",
+            );
+        }
+        if index >= 7 && beyond(&zones[index - 7..=index], 0.0) == 8 {
+            flag(4, "Eight consecutive points on one side of the center line");
+        }
+    }
+
+    violations
+}
+
+// X-bar and R chart limits around a center line
+struct ControlLimits {
+    subgroup_size: u64,
+    average_range: f64,
+    upper: f64,
+    lower: f64,
+    range_upper: f64,
+    range_lower: f64,
+    sigma: f64, // of the subgroup mean
+}
+
+// Days hold varying numbers of inspections; the chart uses the average subgroup size,
+// capped to the constants table. None when no day has more than one sample
+fn control_limits(daily: &[DailyStatistics], center_line: f64) -> Option<ControlLimits> {
+    let multi_sample_days: Vec<&DailyStatistics> = daily
+        .iter()
+        .filter(|day| day.statistics.count > 1)
+        .collect();
+    if multi_sample_days.is_empty() {
+        return None;
+    }
+
+    let subgroup_size = (multi_sample_days
+        .iter()
+        .map(|day| day.statistics.count)
+        .sum::<u64>() as f64
+        / multi_sample_days.len() as f64)
+        .round()
+        .clamp(2.0, 10.0) as usize;
+    let (d2, d3, d4) = CONTROL_CHART_CONSTANTS[subgroup_size - 2];
+    let a2 = 3.0 / (d2 * (subgroup_size as f64).sqrt());
+    let average_range =
+        multi_sample_days.iter().map(|day| day.range).sum::<f64>() / multi_sample_days.len() as f64;
+
+    Some(ControlLimits {
+        subgroup_size: subgroup_size as u64,
+        average_range,
+        upper: center_line + a2 * average_range,
+        lower: center_line - a2 * average_range,
+        range_upper: d4 * average_range,
+        range_lower: d3 * average_range,
+        sigma: a2 * average_range / 3.0,
     })
 }
 
+// X-bar/R control chart, trend and breakdowns for inspection temperature or pH
 #[ic_cdk::query]
-fn get_inventory_analytics(slaughterhouse_id: u64) -> Result<InventoryAnalytics, Message> {
-    let mut product_counts: HashMap<String, u32> = HashMap::new();
-    let mut total_value = 0.0;
-    let mut products_by_status: HashMap<String, Vec<MeatProduct>> = HashMap::new();
-    let mut low_stock_items = Vec::new();
+fn get_spc_analytics(payload: SpcQueryPayload) -> Result<SpcReport, Message> {
+    let parameter = payload.parameter.trim().to_lowercase();
+    if parameter != "temperature" && parameter != "ph" {
+        return Err(Message::invalid(
+            "parameter",
+            FieldErrorCode::InvalidValue,
+            "Parameter must be temperature or ph",
+        ));
+    }
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&payload.slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found(
+            "slaughterhouse",
+            payload.slaughterhouse_id,
+        ));
+    }
+    let species_filter = payload.species.as_deref().map(species_code);
 
-    MEAT_PRODUCTS.with(|products| {
-        products
-            .borrow()
-            .iter()
-            .filter(|(_, product)| product.slaughterhouse_id == slaughterhouse_id)
-            .for_each(|(_, product)| {
-                *product_counts
-                    .entry(product.product_type.clone())
-                    .or_insert(0) += 1;
-                total_value += product.total_price;
+    let mut values = Vec::new();
+    let mut by_day: BTreeMap<u64, Vec<f64>> = BTreeMap::new();
+    let mut by_species: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    let mut by_inspector: BTreeMap<String, Vec<f64>> = BTreeMap::new();
 
-                products_by_status
-                    .entry(product.status.clone())
-                    .or_insert_with(Vec::new)
-                    .push(product.clone());
+    QUALITY_INSPECTIONS.with(|inspections| {
+        for (_, inspection) in inspections.borrow().iter() {
+            if inspection.inspection_date < payload.start_date
+                || inspection.inspection_date > payload.end_date
+            {
+                continue;
+            }
+            if payload.inspector_id.is_some() && inspection.inspector_id != payload.inspector_id {
+                continue;
+            }
+            let animal = match ANIMALS.with(|animals| animals.borrow().get(&inspection.animal_id)) {
+                Some(animal) if animal.slaughterhouse_id == payload.slaughterhouse_id => animal,
+                _ => continue,
+            };
+            let species = species_code(&animal.species);
+            if matches!(&species_filter, Some(filter) if *filter != species) {
+                continue;
+            }
+
+            let value = if parameter == "temperature" {
+                inspection.temperature
+            } else {
+                inspection.ph_level
+            };
+            values.push(value);
+            by_day
+                .entry(inspection.inspection_date - inspection.inspection_date % NANOS_PER_DAY)
+                .or_default()
+                .push(value);
+            by_species.entry(species).or_default().push(value);
+            by_inspector
+                .entry(inspection.inspector_name.clone())
+                .or_default()
+                .push(value);
+        }
+    });
 
-                // Consider products with quantity below 10 as low stock
-                if product.weight < 10.0 {
-                    low_stock_items.push(product.clone());
+    let daily: Vec<DailyStatistics> = by_day
+        .into_iter()
+        .map(|(day_start, values)| {
+            let statistics = summarize(&values);
+            DailyStatistics {
+                day_start,
+                range: statistics.max - statistics.min,
+                statistics,
+            }
+        })
+        .collect();
+
+    let mut report = SpcReport {
+        parameter,
+        overall: summarize(&values),
+        by_species: group_statistics(by_species),
+        by_inspector: group_statistics(by_inspector),
+        trend: "stable".to_string(),
+        ..Default::default()
+    };
+    if daily.is_empty() {
+        return Ok(report);
+    }
+
+    let days = daily.len() as f64;
+    report.center_line = daily.iter().map(|day| day.statistics.mean).sum::<f64>() / days;
+    if let Some(limits) = control_limits(&daily, report.center_line) {
+        report.subgroup_size = limits.subgroup_size;
+        report.average_range = limits.average_range;
+        report.upper_control_limit = limits.upper;
+        report.lower_control_limit = limits.lower;
+        report.range_upper_control_limit = limits.range_upper;
+        report.range_lower_control_limit = limits.range_lower;
+
+        let sigma = limits.sigma;
+        report.violations = western_electric_violations(&daily, report.center_line, sigma);
+
+        // Least-squares slope of the daily means; a change over the period smaller
+        // than one sigma of the mean is reported as stable
+        if daily.len() > 1 {
+            let x: Vec<f64> = daily
+                .iter()
+                .map(|day| (day.day_start - daily[0].day_start) as f64 / NANOS_PER_DAY as f64)
+                .collect();
+            let x_mean = x.iter().sum::<f64>() / days;
+            let covariance: f64 = x
+                .iter()
+                .zip(daily.iter())
+                .map(|(x, day)| (x - x_mean) * (day.statistics.mean - report.center_line))
+                .sum();
+            let variance: f64 = x.iter().map(|x| (x - x_mean).powi(2)).sum();
+            report.trend_slope = covariance / variance;
+
+            let change = report.trend_slope * x[x.len() - 1];
+            if change.abs() > sigma {
+                report.trend = if change > 0.0 { "rising" } else { "falling" }.to_string();
+            }
+        }
+    }
+    report.daily = daily;
+
+    Ok(report)
+}
+
+// Inspection volume and failure rate per inspector employee
+#[ic_cdk::query]
+fn get_inspector_analytics(
+    slaughterhouse_id: u64,
+    start_date: u64,
+    end_date: u64,
+) -> Result<Vec<InspectorAnalytics>, Message> {
+    let slaughterhouse_exists =
+        SLAUGHTERHOUSES.with(|houses| houses.borrow().contains_key(&slaughterhouse_id));
+    if !slaughterhouse_exists {
+        return Err(Message::not_found("slaughterhouse", slaughterhouse_id));
+    }
+
+    let mut by_inspector: BTreeMap<u64, InspectorAnalytics> = BTreeMap::new();
+    QUALITY_INSPECTIONS.with(|inspections| {
+        inspections
+            .borrow()
+            .iter()
+            .filter(|(_, inspection)| {
+                inspection.inspection_date >= start_date && inspection.inspection_date <= end_date
+            })
+            .for_each(|(_, inspection)| {
+                let employee = match inspection
+                    .inspector_id
+                    .and_then(|id| EMPLOYEES.with(|employees| employees.borrow().get(&id)))
+                {
+                    Some(employee) if employee.slaughterhouse_id == slaughterhouse_id => employee,
+                    _ => return,
+                };
+
+                let analytics =
+                    by_inspector
+                        .entry(employee.id)
+                        .or_insert_with(|| InspectorAnalytics {
+                            employee_id: employee.id,
+                            name: employee.name,
+                            ..Default::default()
+                        });
+                analytics.total_inspections += 1;
+                if !inspection.passed {
+                    analytics.failed_inspections += 1;
+                }
+                let overridden = INSPECTION_VERDICTS.with(|verdicts| {
+                    verdicts
+                        .borrow()
+                        .get(&inspection.id)
+                        .map(|verdict| verdict.overridden)
+                        .unwrap_or(false)
+                });
+                if overridden {
+                    analytics.overridden_verdicts += 1;
                 }
             });
     });
 
-    Ok(InventoryAnalytics {
-        product_counts,
-        total_inventory_value: total_value,
-        products_by_status,
-        low_stock_items,
-    })
+    Ok(by_inspector
+        .into_values()
+        .map(|mut analytics| {
+            analytics.failure_rate = round2(
+                analytics.failed_inspections as f64 / analytics.total_inspections as f64 * 100.0,
+            );
+            analytics
+        })
+        .collect())
 }
 
 // Exporting the candid interface
 ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheme(tag_number: &str) -> Option<String> {
+        validate_tag(tag_number)
+            .ok()
+            .map(|validation| validation.scheme)
+    }
+
+    fn day(count: u64, mean: f64, range: f64) -> DailyStatistics {
+        DailyStatistics {
+            day_start: 0,
+            statistics: SummaryStatistics {
+                count,
+                mean,
+                ..Default::default()
+            },
+            range,
+        }
+    }
+
+    fn is_blocked(result: &Result<InstrumentReading, Message>) -> bool {
+        matches!(result, Err(Message::Error(detail)) if detail.code == ErrorCode::Blocked)
+    }
+
+    fn error_code<T>(result: &Result<T, Message>) -> Option<ErrorCode> {
+        match result {
+            Ok(_) | Err(Message::Success(_)) => None,
+            Err(Message::Error(detail))
+            | Err(Message::NotFound(detail))
+            | Err(Message::InvalidPayload(detail)) => Some(detail.code),
+        }
+    }
+
+    fn next_id() -> u64 {
+        ID_COUNTER.with(|counter| *counter.borrow().get())
+    }
+
+    fn store_animal(id: u64, status: AnimalStatus) {
+        ANIMALS.with(|animals| {
+            animals.borrow_mut().insert(
+                id,
+                Animal {
+                    id,
+                    status,
+                    ..Default::default()
+                },
+            );
+        });
+    }
+
+    fn treatment(
+        animal_id: u64,
+        administration_day: u64,
+        withdrawal_days: u32,
+    ) -> VeterinaryTreatmentPayload {
+        VeterinaryTreatmentPayload {
+            animal_id,
+            drug_name: "oxytetracycline".to_string(),
+            dose: 10.0,
+            dose_unit: "ml".to_string(),
+            administration_date: administration_day * NANOS_PER_DAY,
+            withdrawal_days,
+            declared_by: "vet".to_string(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn treatment_blocks_slaughter_until_withdrawal_ends() {
+        store_animal(1, AnimalStatus::Received);
+        assert!(ensure_withdrawal_complete(1, 0).is_ok());
+
+        let declared = store_veterinary_treatment(treatment(1, 10, 5), 12 * NANOS_PER_DAY);
+        match &declared {
+            Ok(treatment) => {
+                assert_eq!(treatment.withdrawal_end_date, 15 * NANOS_PER_DAY);
+                assert_eq!(treatment.declared_at, 12 * NANOS_PER_DAY);
+            }
+            Err(_) => panic!("treatment of a received animal should be accepted"),
+        }
+
+        let blocked = ensure_withdrawal_complete(1, 15 * NANOS_PER_DAY - 1);
+        assert!(error_code(&blocked) == Some(ErrorCode::Blocked));
+        assert!(ensure_withdrawal_complete(1, 15 * NANOS_PER_DAY).is_ok());
+
+        // The longest of overlapping withdrawal periods applies
+        assert!(store_veterinary_treatment(treatment(1, 11, 30), 12 * NANOS_PER_DAY).is_ok());
+        let blocked = ensure_withdrawal_complete(1, 20 * NANOS_PER_DAY);
+        assert!(error_code(&blocked) == Some(ErrorCode::Blocked));
+        assert!(ensure_withdrawal_complete(1, 41 * NANOS_PER_DAY).is_ok());
+    }
+
+    #[test]
+    fn treatment_rejects_invalid_declarations() {
+        store_animal(1, AnimalStatus::Received);
+        let now = 12 * NANOS_PER_DAY;
+
+        let too_long = store_veterinary_treatment(treatment(1, 10, MAX_WITHDRAWAL_DAYS + 1), now);
+        assert!(error_code(&too_long) == Some(ErrorCode::ValidationFailed));
+        let future = store_veterinary_treatment(treatment(1, 13, 5), now);
+        assert!(error_code(&future) == Some(ErrorCode::ValidationFailed));
+        let unknown = store_veterinary_treatment(treatment(2, 10, 5), now);
+        assert!(error_code(&unknown) == Some(ErrorCode::ValidationFailed));
+
+        store_animal(3, AnimalStatus::Processed);
+        let processed = store_veterinary_treatment(treatment(3, 10, 5), now);
+        assert!(error_code(&processed) == Some(ErrorCode::InvalidState));
+        assert!(ensure_withdrawal_complete(3, now).is_ok());
+    }
+
+    #[test]
+    fn withdrawal_end_date_saturates() {
+        assert_eq!(withdrawal_end_date(NANOS_PER_DAY, 0), NANOS_PER_DAY);
+        assert_eq!(
+            withdrawal_end_date(u64::MAX - NANOS_PER_DAY, MAX_WITHDRAWAL_DAYS),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn rejected_expense_category_uses_no_id() {
+        let before = next_id();
+        let rejected = register_expense_category(ExpenseCategoryPayload {
+            code: "feed costs!".to_string(),
+            name: "Feed".to_string(),
+            parent_code: Some("no_such_parent".to_string()),
+        });
+        assert!(error_code(&rejected) == Some(ErrorCode::ValidationFailed));
+        assert_eq!(next_id(), before);
+    }
+
+    #[test]
+    fn ccp_monitoring_interval_is_bounded() {
+        SLAUGHTERHOUSES.with(|houses| {
+            houses.borrow_mut().insert(1, Slaughterhouse::default());
+        });
+        let before = next_id();
+        let rejected = define_ccp(CriticalControlPointPayload {
+            slaughterhouse_id: 1,
+            name: "Chilling".to_string(),
+            hazard: "Bacterial growth".to_string(),
+            parameter: "temperature".to_string(),
+            unit: "C".to_string(),
+            min_limit: 0.0,
+            max_limit: 7.0,
+            monitoring_interval_minutes: MAX_MONITORING_INTERVAL_MINUTES + 1,
+        });
+        match &rejected {
+            Err(Message::InvalidPayload(detail)) => {
+                let fields: Vec<&str> = detail
+                    .field_errors
+                    .iter()
+                    .map(|error| error.field.as_str())
+                    .collect();
+                assert_eq!(fields, ["monitoring_interval_minutes"]);
+            }
+            _ => panic!("an interval above the limit should be rejected"),
+        }
+        assert_eq!(next_id(), before);
+    }
+
+    #[test]
+    fn shelf_life_runs_from_production() {
+        SPECIES_CATALOG.with(|catalog| {
+            catalog.borrow_mut().insert(
+                1,
+                Species {
+                    id: 1,
+                    code: "cattle".to_string(),
+                    shelf_life_days: 10,
+                    ..Default::default()
+                },
+            );
+        });
+        ANIMALS.with(|animals| {
+            animals.borrow_mut().insert(
+                2,
+                Animal {
+                    id: 2,
+                    species: "Cattle".to_string(),
+                    ..Default::default()
+                },
+            );
+        });
+        let product = MeatProduct {
+            animal_id: 2,
+            created_at: 5 * NANOS_PER_DAY,
+            ..Default::default()
+        };
+        assert!(!is_past_shelf_life(&product, 15 * NANOS_PER_DAY));
+        assert!(is_past_shelf_life(&product, 15 * NANOS_PER_DAY + 1));
+
+        let late = MeatProduct {
+            created_at: u64::MAX - NANOS_PER_DAY,
+            ..product
+        };
+        assert!(!is_past_shelf_life(&late, u64::MAX));
+        let unknown = MeatProduct {
+            animal_id: 3,
+            ..late
+        };
+        assert!(!is_past_shelf_life(&unknown, u64::MAX));
+    }
+
+    #[test]
+    fn text_limits_count_bytes() {
+        let ascii = "a".repeat(MAX_NAME_LENGTH);
+        let accented = "é".repeat(MAX_NAME_LENGTH / 2 + 1);
+        assert!(Validator::new()
+            .text("name", &ascii, MAX_NAME_LENGTH)
+            .finish()
+            .is_ok());
+
+        match Validator::new()
+            .text("name", &accented, MAX_NAME_LENGTH)
+            .finish()
+        {
+            Err(Message::InvalidPayload(detail)) => {
+                assert!(detail.field_errors[0].code == FieldErrorCode::TooLong);
+                assert_eq!(
+                    detail.field_errors[0].message,
+                    "name is limited to 64 bytes"
+                );
+            }
+            _ => panic!("text over the byte limit should be rejected"),
+        }
+    }
+
+    #[test]
+    fn validate_tag_normalizes_and_detects_scheme() {
+        assert_eq!(scheme("840 0030-0123-4567").as_deref(), Some("USDA 840"));
+        assert_eq!(
+            scheme("276098765432100").as_deref(),
+            Some("ISO 11784/11785")
+        );
+        assert_eq!(scheme("uk 123456 700001").as_deref(), Some("UK/EU visual"));
+        assert_eq!(
+            validate_tag("uk 123456-700001")
+                .ok()
+                .map(|validation| validation.tag_number),
+            Some("UK123456700001".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_tag_rejects_unknown_formats() {
+        assert_eq!(scheme(""), None);
+        assert_eq!(scheme("000123456789012"), None);
+        assert_eq!(scheme("84000300123456"), None);
+        assert_eq!(scheme("ZZ123456"), None);
+        assert_eq!(scheme("UK12345"), None);
+        assert_eq!(scheme("UK12345A789"), None);
+    }
+
+    #[test]
+    fn control_limits_use_average_subgroup_size() {
+        let daily = [day(5, 4.0, 1.0), day(5, 6.0, 3.0), day(1, 5.0, 0.0)];
+        let limits = control_limits(&daily, 5.0).expect("limits for multi-sample days");
+
+        // n = 5: d2 = 2.326, D3 = 0, D4 = 2.114, so A2 = 3 / (d2 * sqrt(5)) = 0.577
+        assert_eq!(limits.subgroup_size, 5);
+        assert_close(limits.average_range, 2.0);
+        assert_close(limits.upper, 5.0 + 0.577 * 2.0);
+        assert_close(limits.lower, 5.0 - 0.577 * 2.0);
+        assert_close(limits.range_upper, 2.114 * 2.0);
+        assert_close(limits.range_lower, 0.0);
+        assert_close(limits.sigma, 0.577 * 2.0 / 3.0);
+    }
+
+    #[test]
+    fn control_limits_clamp_subgroup_size() {
+        let limits = control_limits(&[day(40, 5.0, 1.0)], 5.0).expect("limits");
+        assert_eq!(limits.subgroup_size, 10);
+        assert_close(limits.range_lower, 0.223);
+
+        assert!(control_limits(&[day(1, 5.0, 0.0), day(1, 6.0, 0.0)], 5.5).is_none());
+    }
+
+    #[test]
+    fn check_reading_without_instrument() {
+        assert!(is_blocked(&check_reading(None, "temperature", 1)));
+        assert!(is_blocked(&check_reading(None, "ph", 1)));
+
+        match check_reading(None, "weight", 1) {
+            Ok(reading) => {
+                assert!(reading.flagged);
+                assert_eq!(reading.flag_reason, "No instrument recorded");
+                assert_eq!(reading.slaughterhouse_id, 1);
+            }
+            Err(_) => panic!("weight readings without a scale should only be flagged"),
+        }
+    }
+
+    #[test]
+    fn abp_routes_narrow_with_category() {
+        assert!(disposal_route_permitted(
+            AbpCategory::Category1,
+            "incineration"
+        ));
+        assert!(!disposal_route_permitted(
+            AbpCategory::Category1,
+            "rendering"
+        ));
+        assert!(!disposal_route_permitted(
+            AbpCategory::Category2,
+            "pet_food"
+        ));
+        assert!(disposal_route_permitted(AbpCategory::Category2, "biogas"));
+        assert!(disposal_route_permitted(AbpCategory::Category3, "pet_food"));
+        assert!(!disposal_route_permitted(
+            AbpCategory::Category3,
+            "landfill"
+        ));
+    }
+
+    #[test]
+    fn salary_is_prorated_from_period_start_or_hire_date() {
+        let start = 1_000 * NANOS_PER_DAY;
+        let end = start + 73 * NANOS_PER_DAY;
+
+        assert_close(prorated_salary(36_500.0, start, end, 0), 7_300.0);
+        assert_close(
+            prorated_salary(36_500.0, start, end, start + 30 * NANOS_PER_DAY),
+            4_300.0,
+        );
+        assert_close(prorated_salary(36_500.0, start, end, end), 0.0);
+        assert_close(
+            prorated_salary(36_500.0, start, end, end + NANOS_PER_DAY),
+            0.0,
+        );
+    }
+
+    #[test]
+    fn calibration_requires_active_instrument_before_due_date() {
+        let instrument = Instrument {
+            calibration_due_at: 100,
+            ..Default::default()
+        };
+        assert!(is_in_calibration(&instrument, 99));
+        assert!(!is_in_calibration(&instrument, 100));
+        assert!(!is_in_calibration(&Instrument::default(), 0));
+
+        let retired = Instrument {
+            status: InstrumentStatus::Retired,
+            ..instrument
+        };
+        assert!(!is_in_calibration(&retired, 99));
+    }
+}